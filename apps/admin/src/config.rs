@@ -0,0 +1,271 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use blog_core::client::Client;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+pub const DEFAULT_PROFILE: &str = "default";
+const DEFAULT_BASE_URL: &str = "http://localhost:8080/";
+const DEFAULT_TOKEN_VARIABLE: &str = "ADMIN_TOKEN";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot find the configuration directory, set ADMIN_CONFIG or HOME")]
+    NoConfigDirectory,
+    #[error("Cannot read {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("Cannot write {0}: {1}")]
+    Write(PathBuf, io::Error),
+    #[error("Invalid configuration in {0}: {1}")]
+    Invalid(PathBuf, toml::de::Error),
+    #[error("Cannot serialize the configuration: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("There's no profile named {0}")]
+    UnknownProfile(String),
+    #[error("The environment variable {0} with the token is not set")]
+    MissingVariable(String),
+    #[error("The token command `{0}` failed: {1}")]
+    Command(String, String),
+    #[error("The token is empty")]
+    EmptyToken,
+}
+
+/// Where the token of a profile comes from, so that it's never stored in the config file itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenSource {
+    /// The name of an environment variable
+    Env(String),
+    /// A file with only the token, e.g. the one written by `admin login`
+    File(PathBuf),
+    /// A shell command that prints the token, e.g. `pass show ramona.fun/admin`
+    Command(String),
+}
+
+impl TokenSource {
+    pub fn read(&self) -> Result<String, Error> {
+        let token = match self {
+            Self::Env(name) => {
+                std::env::var(name).map_err(|_| Error::MissingVariable(name.clone()))?
+            }
+            Self::File(path) => {
+                fs::read_to_string(path).map_err(|e| Error::Read(path.clone(), e))?
+            }
+            Self::Command(command) => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .output()
+                    .map_err(|e| Error::Command(command.clone(), e.to_string()))?;
+
+                if !output.status.success() {
+                    return Err(Error::Command(
+                        command.clone(),
+                        String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    ));
+                }
+
+                String::from_utf8_lossy(&output.stdout).into_owned()
+            }
+        };
+
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(Error::EmptyToken);
+        }
+
+        Ok(token.to_string())
+    }
+}
+
+impl std::fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Env(name) => write!(f, "env {name}"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Command(command) => write!(f, "command `{command}`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub base_url: Url,
+    pub token: TokenSource,
+}
+
+impl Profile {
+    /// Requests are signed with the token, unless `sign` is `false`, then the token itself is sent
+    /// in `X-Token`.
+    pub fn client(&self, sign: bool) -> Result<Client, Error> {
+        Ok(Client::new(self.base_url.clone(), self.token.read()?, sign))
+    }
+}
+
+impl Default for Profile {
+    /// The local development server, with the token in `ADMIN_TOKEN`.
+    fn default() -> Self {
+        Self {
+            base_url: Url::parse(DEFAULT_BASE_URL).expect("The default base URL is valid"),
+            token: TokenSource::Env(DEFAULT_TOKEN_VARIABLE.to_string()),
+        }
+    }
+}
+
+/// The contents of `config.toml`, e.g.:
+///
+/// ```toml
+/// default_profile = "production"
+///
+/// [profiles.production]
+/// base_url = "https://ramona.fun/"
+/// token = { command = "pass show ramona.fun/admin" }
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    /// `$ADMIN_CONFIG` if it's set, `$XDG_CONFIG_HOME/ramona-admin` or `~/.config/ramona-admin`
+    /// otherwise.
+    pub fn directory() -> Result<PathBuf, Error> {
+        let env = |name| std::env::var_os(name).filter(|x| !x.is_empty());
+
+        if let Some(directory) = env("ADMIN_CONFIG") {
+            return Ok(PathBuf::from(directory));
+        }
+
+        env("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env("HOME").map(|x| PathBuf::from(x).join(".config")))
+            .map(|x| x.join("ramona-admin"))
+            .ok_or(Error::NoConfigDirectory)
+    }
+
+    /// A missing file is the same as an empty one.
+    pub fn load(directory: &Path) -> Result<Self, Error> {
+        let path = directory.join("config.toml");
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| Error::Invalid(path, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::Read(path, e)),
+        }
+    }
+
+    pub fn save(&self, directory: &Path) -> Result<(), Error> {
+        write_private(&directory.join("config.toml"), &toml::to_string(self)?)
+    }
+
+    /// The name of the profile to use, when none was selected explicitly.
+    pub fn default_profile_name(&self) -> &str {
+        self.default_profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    /// Without any profiles configured, the `default` one points at the local development server.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, Error> {
+        let name = name.unwrap_or_else(|| self.default_profile_name());
+
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile.clone()),
+            None if name == DEFAULT_PROFILE && self.profiles.is_empty() => Ok(Profile::default()),
+            None => Err(Error::UnknownProfile(name.to_string())),
+        }
+    }
+}
+
+/// Writes the file readable only by the current user, as it may contain a token.
+pub fn write_private(path: &Path, contents: &str) -> Result<(), Error> {
+    let error = |e| Error::Write(path.to_path_buf(), e);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(error)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path).map_err(error)?;
+    // The mode only applies to new files, an existing one may be readable by others
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))
+        .map_err(error)?;
+
+    file.write_all(contents.as_bytes()).map_err(error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn can_parse_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+                default_profile = "production"
+
+                [profiles.production]
+                base_url = "https://ramona.fun/"
+                token = { command = "echo secret" }
+
+                [profiles.local]
+                base_url = "http://localhost:8080/"
+                token = { env = "LOCAL_TOKEN" }
+            "#,
+        )
+        .unwrap();
+
+        let production = config.profile(None).unwrap();
+        assert_eq!("https://ramona.fun/", production.base_url.as_str());
+        assert_eq!("secret", production.token.read().unwrap());
+
+        assert_eq!(
+            TokenSource::Env("LOCAL_TOKEN".to_string()),
+            config.profile(Some("local")).unwrap().token
+        );
+        assert!(config.profile(Some("staging")).is_err());
+        assert_eq!(
+            config,
+            toml::from_str(&toml::to_string(&config).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn defaults_to_the_local_server() {
+        let config = Config::default();
+
+        assert_eq!(Profile::default(), config.profile(None).unwrap());
+        assert!(config.profile(Some("production")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    pub fn private_files_are_readable_only_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("token");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private(&path, "new").unwrap();
+
+        assert_eq!("new", fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            0o600,
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777
+        );
+    }
+}
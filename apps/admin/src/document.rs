@@ -0,0 +1,347 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot read {0}: {1}")]
+    Read(String, std::io::Error),
+    #[error("Cannot write {0}: {1}")]
+    Write(String, std::io::Error),
+    #[error("The front matter is not closed with {0}")]
+    Unterminated(&'static str),
+    #[error("Invalid YAML front matter: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid TOML front matter: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("The front matter has no title")]
+    MissingTitle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Between `---` lines
+    Yaml,
+    /// Between `+++` lines
+    Toml,
+}
+
+impl Format {
+    const fn delimiter(self) -> &'static str {
+        match self {
+            Self::Yaml => "---",
+            Self::Toml => "+++",
+        }
+    }
+
+    fn id_line(self, id: Uuid) -> String {
+        match self {
+            Self::Yaml => format!("id: {id}\n"),
+            Self::Toml => format!("id = \"{id}\"\n"),
+        }
+    }
+}
+
+/// The metadata at the top of a post's markdown file, e.g.:
+///
+/// ```markdown
+/// ---
+/// title: Hello
+/// slug: hello
+/// tags: [meta]
+/// date: 2026-10-19T10:00:00Z
+/// description: The first post
+/// ---
+/// ```
+///
+/// The `id` is added by the CLI once the post is published for the first time.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct FrontMatter {
+    pub id: Option<Uuid>,
+    pub title: Option<String>,
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// RFC 3339, or only the day, which means midnight UTC
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub date: Option<OffsetDateTime>,
+    pub description: Option<String>,
+}
+
+fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<OffsetDateTime>, D::Error> {
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    OffsetDateTime::parse(&value, &Rfc3339)
+        .or_else(|_| {
+            Date::parse(&value, format_description!("[year]-[month]-[day]"))
+                .map(|x| x.midnight().assume_utc())
+        })
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("invalid date {value}")))
+}
+
+/// Where the front matter is in the text.
+struct Split<'a> {
+    format: Format,
+    /// The byte offset of the first line after the opening delimiter
+    start: usize,
+    front_matter: &'a str,
+    body: &'a str,
+}
+
+fn split(text: &str) -> Result<Option<Split<'_>>, Error> {
+    let mut lines = text.split_inclusive('\n');
+    let Some(first) = lines.next() else {
+        return Ok(None);
+    };
+
+    let format = match first.trim_end() {
+        "---" => Format::Yaml,
+        "+++" => Format::Toml,
+        _ => return Ok(None),
+    };
+
+    let start = first.len();
+    let mut end = start;
+    for line in lines {
+        if line.trim_end() == format.delimiter() {
+            return Ok(Some(Split {
+                format,
+                start,
+                front_matter: &text[start..end],
+                body: &text[end + line.len()..],
+            }));
+        }
+
+        end += line.len();
+    }
+
+    Err(Error::Unterminated(format.delimiter()))
+}
+
+fn parse_front_matter(format: Format, front_matter: &str) -> Result<FrontMatter, Error> {
+    match format {
+        Format::Yaml if front_matter.trim().is_empty() => Ok(FrontMatter::default()),
+        Format::Yaml => Ok(serde_yaml::from_str(front_matter)?),
+        Format::Toml => {
+            let mut table: toml::Table = toml::from_str(front_matter)?;
+
+            // TOML has its own dates, they're parsed like the YAML ones instead
+            if let Some(toml::Value::Datetime(date)) = table.get("date") {
+                let date = date.to_string();
+                table.insert("date".to_string(), toml::Value::String(date));
+            }
+
+            Ok(table.try_into()?)
+        }
+    }
+}
+
+/// A post's markdown file.
+#[derive(Debug)]
+pub struct Document {
+    pub front_matter: FrontMatter,
+    /// The markdown after the front matter
+    pub content: String,
+    text: String,
+}
+
+impl Document {
+    pub fn parse(text: String) -> Result<Self, Error> {
+        let (front_matter, content) = match split(&text)? {
+            Some(split) => (
+                parse_front_matter(split.format, split.front_matter)?,
+                split.body.trim_start_matches(['\r', '\n']).to_string(),
+            ),
+            None => (FrontMatter::default(), text.clone()),
+        };
+
+        Ok(Self {
+            front_matter,
+            content,
+            text,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::Read(path.display().to_string(), e))?;
+
+        Self::parse(text)
+    }
+
+    pub fn title(&self) -> Result<&str, Error> {
+        self.front_matter
+            .title
+            .as_deref()
+            .filter(|x| !x.trim().is_empty())
+            .ok_or(Error::MissingTitle)
+    }
+
+    /// Sets the ID in the front matter, everything else in the file stays as it was. Adds YAML
+    /// front matter if there's none.
+    pub fn set_id(&mut self, id: Uuid) -> Result<(), Error> {
+        self.text = match split(&self.text)? {
+            Some(split) => {
+                let front_matter: String = split
+                    .front_matter
+                    .split_inclusive('\n')
+                    .filter(|line| !is_id_line(line))
+                    .collect();
+
+                format!(
+                    "{}{}{front_matter}{}",
+                    &self.text[..split.start],
+                    split.format.id_line(id),
+                    &self.text[split.start + split.front_matter.len()..]
+                )
+            }
+            None => format!("---\n{}---\n\n{}", Format::Yaml.id_line(id), self.text),
+        };
+        self.front_matter.id = Some(id);
+
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, &self.text).map_err(|e| Error::Write(path.display().to_string(), e))
+    }
+
+    /// The ID from the front matter, or `new_id`, which is saved into the file right away, so
+    /// that the file always refers to the same post.
+    pub fn ensure_id(&mut self, path: &Path, new_id: Uuid) -> Result<Uuid, Error> {
+        if let Some(id) = self.front_matter.id {
+            return Ok(id);
+        }
+
+        let id = new_id;
+        self.set_id(id)?;
+        self.save(path)?;
+
+        Ok(id)
+    }
+}
+
+/// Every `.md` file in the directory and the ones below it, skipping the hidden ones, like `.git`.
+fn find_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path
+            .file_name()
+            .is_some_and(|x| x.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else if path.extension().is_some_and(|x| x == "md") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The markdown files in the directory, sorted.
+pub fn find_all(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    find_files(directory, &mut files)?;
+    files.sort();
+
+    Ok(files)
+}
+
+fn is_id_line(line: &str) -> bool {
+    line.strip_prefix("id")
+        .is_some_and(|x| x.trim_start().starts_with([':', '=']))
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    pub fn can_parse_yaml_front_matter() {
+        let document = Document::parse(
+            "---\ntitle: Hello\nslug: hello\ntags: [meta, rust]\ndate: 2026-10-19\ndescription: The first post\n---\n\n# Hello\n"
+                .to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            FrontMatter {
+                id: None,
+                title: Some("Hello".to_string()),
+                slug: Some("hello".to_string()),
+                tags: vec!["meta".to_string(), "rust".to_string()],
+                date: Some(datetime!(2026-10-19 00:00 UTC)),
+                description: Some("The first post".to_string()),
+            },
+            document.front_matter
+        );
+        assert_eq!("# Hello\n", document.content);
+    }
+
+    #[test]
+    pub fn can_parse_toml_front_matter() {
+        let document = Document::parse(
+            "+++\ntitle = \"Hello\"\ndate = 2026-10-19T10:00:00+02:00\n+++\nText\n".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(Some("Hello"), document.front_matter.title.as_deref());
+        assert_eq!(
+            Some(datetime!(2026-10-19 08:00 UTC)),
+            document.front_matter.date
+        );
+        assert_eq!("Text\n", document.content);
+    }
+
+    #[test]
+    pub fn writes_the_id_into_the_front_matter() {
+        let id = Uuid::new_v4();
+
+        let mut yaml = Document::parse("---\ntitle: Hello\nid:\n---\nText\n".to_string()).unwrap();
+        yaml.set_id(id).unwrap();
+        assert_eq!(
+            format!("---\nid: {id}\ntitle: Hello\n---\nText\n"),
+            yaml.text
+        );
+
+        let mut toml = Document::parse("+++\ntitle = \"Hello\"\n+++\nText\n".to_string()).unwrap();
+        toml.set_id(id).unwrap();
+        assert_eq!(
+            format!("+++\nid = \"{id}\"\ntitle = \"Hello\"\n+++\nText\n"),
+            toml.text
+        );
+        assert_eq!(
+            Some(id),
+            Document::parse(toml.text).unwrap().front_matter.id
+        );
+
+        let mut none = Document::parse("Text\n".to_string()).unwrap();
+        none.set_id(id).unwrap();
+        assert_eq!(format!("---\nid: {id}\n---\n\nText\n"), none.text);
+    }
+
+    #[test]
+    pub fn requires_a_closed_front_matter() {
+        assert!(Document::parse("---\ntitle: Hello\n".to_string()).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use blog_core::{
+    api::{content_sha256, DraftPutRequest},
+    client::{self, Client},
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    document::{self, Document},
+    media::{self, prepare, upload},
+    watch::Watcher,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot watch {0}: {1}")]
+    Watch(String, io::Error),
+    #[error("{0}")]
+    Document(#[from] document::Error),
+    #[error("{0}")]
+    Media(#[from] media::Error),
+    #[error("{0}")]
+    Client(#[from] client::Error),
+}
+
+/// Pushes the files as drafts, remembering what was pushed, so that saving a file without
+/// changing it doesn't send it again.
+struct Drafts<'a> {
+    client: &'a Client,
+    /// The directory being watched, the files the drafts link to have to be in it, or in the one
+    /// of the file if `None`
+    root: Option<&'a Path>,
+    pushed: HashMap<PathBuf, String>,
+    /// The IDs of the files that have none, the files are not written while they may be open in
+    /// an editor
+    ids: HashMap<PathBuf, Uuid>,
+}
+
+impl Drafts<'_> {
+    /// Returns the ID of the draft, or `None` if nothing changed since the last push.
+    async fn push(&mut self, path: &Path) -> Result<Option<Uuid>, Error> {
+        let document = Document::load(path)?;
+        let title = document.title()?.to_string();
+        let id = match document.front_matter.id {
+            Some(id) => id,
+            None => *self
+                .ids
+                .entry(path.to_path_buf())
+                .or_insert_with(Uuid::new_v4),
+        };
+        let prepared = prepare(&document.content, path, self.root)?;
+
+        let front_matter = document.front_matter;
+        let draft = DraftPutRequest {
+            title,
+            content: prepared.content,
+            slug: front_matter.slug,
+            description: front_matter.description,
+            tags: front_matter.tags,
+        };
+
+        let hash = content_sha256(&format!(
+            "{id}\n{}",
+            serde_json::to_string(&draft).expect("The draft can be serialized")
+        ));
+        if self.pushed.get(path) == Some(&hash) {
+            return Ok(None);
+        }
+
+        upload(self.client, &prepared.files).await?;
+        let result = self.client.put_draft(id, &draft).await;
+        // The server would reject the same draft again, only a connection failure is worth retrying
+        if !matches!(result, Err(client::Error::Http(_))) {
+            self.pushed.insert(path.to_path_buf(), hash);
+        }
+        result?;
+
+        Ok(Some(id))
+    }
+
+    /// Errors only mean the file is not ready yet, so they're printed and the watching goes on.
+    async fn push_and_report(&mut self, path: &Path) {
+        match self.push(path).await {
+            Ok(Some(id)) => match self.client.draft_url(id) {
+                Ok(url) => eprintln!(
+                    "Pushed {}, preview at {url} (needs signing in with a passkey)",
+                    path.display()
+                ),
+                Err(e) => eprintln!("Pushed {}, {e}", path.display()),
+            },
+            Ok(None) => {}
+            Err(e) => eprintln!("{}: {e}", path.display()),
+        }
+    }
+}
+
+/// Pushes the file, or the markdown files in the directory, as drafts every time they're saved,
+/// until it's stopped. The published posts don't change, `admin post` publishes the drafts.
+///
+/// The IDs are written into the files without one when the watching starts, before they're
+/// edited, so that the drafts and the published posts have the same ID. The files created later
+/// get an ID until the watching stops.
+pub async fn watch(client: &Client, path: &Path) -> Result<(), Error> {
+    std::fs::metadata(path).map_err(|e| Error::Watch(path.display().to_string(), e))?;
+
+    let mut drafts = Drafts {
+        client,
+        root: path.is_dir().then_some(path),
+        pushed: HashMap::new(),
+        ids: HashMap::new(),
+    };
+    let mut watcher = Watcher::new(path);
+
+    let files: Vec<PathBuf> = watcher.files().map(Path::to_path_buf).collect();
+    for file in &files {
+        let result = Document::load(file).and_then(|mut x| x.ensure_id(file, Uuid::new_v4()));
+        if let Err(e) = result {
+            eprintln!("{}: {e}", file.display());
+        }
+    }
+
+    eprintln!("Watching {}, stop with Ctrl+C", path.display());
+
+    for file in files {
+        drafts.push_and_report(&file).await;
+    }
+
+    loop {
+        for file in watcher.changed().await {
+            drafts.push_and_report(&file).await;
+        }
+    }
+}
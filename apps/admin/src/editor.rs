@@ -0,0 +1,73 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::config::write_private;
+
+const DEFAULT_EDITOR: &str = "vi";
+
+/// The text saved in the editor. The file stays until [`Edited::remove`], so that the changes
+/// aren't lost if they can't be uploaded.
+pub struct Edited {
+    pub text: String,
+    directory: PathBuf,
+    path: PathBuf,
+}
+
+impl Edited {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn remove(self) -> io::Result<()> {
+        std::fs::remove_dir_all(&self.directory)
+    }
+}
+
+/// Opens the text in `$VISUAL` or `$EDITOR` and returns it once the editor exits. `file_name`
+/// is there for the editor to recognize the file type.
+pub fn edit(text: &str, file_name: &str) -> io::Result<Edited> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+
+    // A new directory only the current user can access, it's removed by the caller
+    let mut builder = tempfile::Builder::new();
+    builder.prefix("admin-");
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+    let directory = builder.tempdir()?.keep();
+    let path = directory.join(file_name);
+    write_private(&path, text).map_err(io::Error::other)?;
+
+    // Through the shell, as the editor can come with arguments, e.g. `code --wait`
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("sh")
+        .arg(&path)
+        .status();
+
+    let result = status.and_then(|status| {
+        if !status.success() {
+            return Err(io::Error::other(format!("The editor exited with {status}")));
+        }
+
+        std::fs::read_to_string(&path)
+    });
+
+    match result {
+        Ok(text) => Ok(Edited {
+            text,
+            directory,
+            path,
+        }),
+        Err(e) => {
+            std::fs::remove_dir_all(&directory)?;
+
+            Err(e)
+        }
+    }
+}
@@ -1,55 +1,403 @@
-use std::path::PathBuf;
-use clap::{Parser, Subcommand};
+use blog_core::{
+    api::{PostUpdateRequest, PostsQuery},
+    client::Client,
+};
+use clap::{Args, Parser, Subcommand};
+use config::{Config, Profile, TokenSource};
+use output::Output;
 use reqwest::Url;
-use serde::Serialize;
+use std::error::Error;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use uuid::Uuid;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    #[arg(short, long)]
-    token: String,
+    /// The profile from the config file, the default one if not set
+    #[arg(short, long, global = true, env = "ADMIN_PROFILE")]
+    profile: Option<String>,
+    /// Send the token in the X-Token header, instead of signing the requests with it
+    #[arg(long, global = true)]
+    no_sign: bool,
+    #[arg(short, long, global = true, value_enum, default_value_t)]
+    output: Output,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    #[command(flatten)]
+    Blog(BlogCommands),
+    /// Saves the profile selected with --profile, prompting for the token unless it's read from
+    /// an environment variable or a command
+    Login(LoginArgs),
+    /// Lists the profiles
+    Profiles {
+        #[command(subcommand)]
+        command: Option<ProfilesCommand>,
+    },
+}
+
+/// The commands that call the blog with the token of the profile.
+#[derive(Subcommand)]
+enum BlogCommands {
+    /// Publishes a markdown file with front matter. The ID of the new post is written into the
+    /// front matter, so that publishing the file again updates the same post.
     Post {
-        title: String,
-        content: PathBuf
+        file: PathBuf,
+    },
+    /// Serves the markdown file rendered like the public page, the page reloads when the file is
+    /// saved. The rendering and the CSS come from the blog, nothing is saved there.
+    Preview {
+        file: PathBuf,
+        #[arg(long, default_value = "127.0.0.1:4000")]
+        listen: SocketAddr,
+    },
+    /// Pushes the file, or the markdown files in the directory, as drafts whenever they're saved,
+    /// and prints where to preview them, which needs signing in to the blog with a passkey. The
+    /// published posts don't change until `post`.
+    Watch {
+        path: PathBuf,
+    },
+    /// Makes the blog match a directory of markdown files: creates and updates the posts from the
+    /// files, restores the deleted ones that have a file, and deletes the ones without a file.
+    /// Files without an ID get one derived from their path, which is written into them too, commit
+    /// it so that the post is kept when the file is renamed.
+    Sync {
+        directory: PathBuf,
+        /// Apply the changes without asking, e.g. in CI
+        #[arg(long)]
+        yes: bool,
+        /// Allow deleting every post, when the directory has none of them
+        #[arg(long)]
+        delete_all: bool,
+    },
+    /// Lists the posts, including the scheduled ones
+    List {
+        /// List the deleted posts instead
+        #[arg(long)]
+        deleted: bool,
+    },
+    Show {
+        id: Uuid,
+    },
+    /// Opens the content of the post in $EDITOR, and uploads it once the editor exits
+    Edit {
+        id: Uuid,
+    },
+    #[command(group = clap::ArgGroup::new("changes").required(true).multiple(true))]
+    Update {
+        id: Uuid,
+        #[arg(long, group = "changes")]
+        title: Option<String>,
+        /// A file with the new content
+        #[arg(long, group = "changes")]
+        content: Option<PathBuf>,
+    },
+    /// Deletes the post, it can be restored later
+    Delete {
+        id: Uuid,
+    },
+    Restore {
+        id: Uuid,
+    },
+    /// Sets the publication date, a date in the future schedules the post
+    Publish {
+        id: Uuid,
+        /// RFC 3339, e.g. 2026-10-20T08:00:00Z, now if not set
+        #[arg(long, value_parser = parse_date)]
+        at: Option<OffsetDateTime>,
+    },
+}
+
+#[derive(Args)]
+struct LoginArgs {
+    #[arg(long)]
+    base_url: Url,
+    /// Read the token from this environment variable
+    #[arg(long, conflicts_with = "token_command")]
+    token_env: Option<String>,
+    /// Read the token from the output of this shell command
+    #[arg(long)]
+    token_command: Option<String>,
+    /// Use this profile when --profile is not set
+    #[arg(long)]
+    default: bool,
+}
+
+#[derive(Subcommand)]
+enum ProfilesCommand {
+    /// Makes the profile the default one
+    Use { name: String },
+    /// Removes the profile, and its token if it was saved by `login`
+    Remove { name: String },
+}
+
+mod config;
+mod document;
+mod draft;
+mod editor;
+mod media;
+mod output;
+mod preview;
+mod publish;
+mod sync;
+mod watch;
+
+fn parse_date(value: &str) -> Result<OffsetDateTime, time::error::Parse> {
+    OffsetDateTime::parse(value, &Rfc3339)
+}
+
+/// The token saved by `login` for the profile.
+fn token_path(directory: &Path, profile: &str) -> PathBuf {
+    directory.join("tokens").join(profile)
+}
+
+/// Prompts without echoing the token, or reads it from the standard input when it's piped.
+fn read_token(base_url: &Url) -> std::io::Result<String> {
+    if std::io::stdin().is_terminal() {
+        return rpassword::prompt_password(format!("Token for {base_url}: "));
     }
+
+    let mut token = String::new();
+    std::io::stdin().read_line(&mut token)?;
+
+    Ok(token)
 }
 
-#[derive(Serialize)]
-struct CreatePostRequest {
-    id: Uuid,
-    title: String,
-    content: String,
+/// Asks on the terminal, refuses without one, as nobody would answer.
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    if !std::io::stdin().is_terminal() {
+        return Err("Not a terminal, pass --yes to apply the changes".into());
+    }
+
+    eprint!("{question} [y/N] ");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn login(
+    directory: &Path,
+    mut config: Config,
+    name: &str,
+    args: LoginArgs,
+) -> Result<(), Box<dyn Error>> {
+    let token = match (args.token_env, args.token_command) {
+        (Some(name), _) => TokenSource::Env(name),
+        (_, Some(command)) => TokenSource::Command(command),
+        (None, None) => {
+            let token = read_token(&args.base_url)?;
+            let path = token_path(directory, name);
+            config::write_private(&path, token.trim())?;
+
+            TokenSource::File(path)
+        }
+    };
+
+    // Fail early, rather than on the first request
+    token.read()?;
+
+    if args.default || config.profiles.is_empty() {
+        config.default_profile = Some(name.to_string());
+    }
+    config.profiles.insert(
+        name.to_string(),
+        Profile {
+            base_url: args.base_url,
+            token,
+        },
+    );
+    config.save(directory)?;
+
+    println!("Saved the profile {name}");
+
+    Ok(())
+}
+
+fn profiles(
+    directory: &Path,
+    mut config: Config,
+    command: Option<ProfilesCommand>,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        None => {
+            for (name, profile) in &config.profiles {
+                let marker = if name == config.default_profile_name() {
+                    "*"
+                } else {
+                    " "
+                };
+
+                println!("{marker} {name}\t{}\t{}", profile.base_url, profile.token);
+            }
+        }
+        Some(ProfilesCommand::Use { name }) => {
+            if !config.profiles.contains_key(&name) {
+                return Err(config::Error::UnknownProfile(name).into());
+            }
+
+            config.default_profile = Some(name);
+            config.save(directory)?;
+        }
+        Some(ProfilesCommand::Remove { name }) => {
+            let profile = config
+                .profiles
+                .remove(&name)
+                .ok_or_else(|| config::Error::UnknownProfile(name.clone()))?;
+
+            if profile.token == TokenSource::File(token_path(directory, &name)) {
+                std::fs::remove_file(token_path(directory, &name))?;
+            }
+            if config.default_profile.as_deref() == Some(name.as_str()) {
+                config.default_profile = None;
+            }
+            config.save(directory)?;
+        }
+    }
+
+    Ok(())
 }
 
-// JRmKu2c6Kfl8ms5mlhMCfkNyzxGrprTRZmpQvPIB0iQcjzVqUgpCjJIGpClqOxEYR7mguSG5JaOuXPxPJe6UWvQSyZK9BT8zdahRuHLKeiiwDsfZ0NcKOVZAz1Ssnqwp
 #[tokio::main]
 async fn main() {
-    let base = Url::parse("http://localhost:8080/").unwrap();
-    let reqwest = reqwest::Client::new();
     let cli = Cli::parse();
 
+    if let Err(e) = run(cli).await {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let directory = Config::directory()?;
+    let config = Config::load(&directory)?;
+
     match cli.command {
-        Commands::Post { title, content } => {
-           println!("Publishing: {} from {:?}", title, content); 
+        Commands::Blog(command) => {
+            let client = config
+                .profile(cli.profile.as_deref())?
+                .client(!cli.no_sign)?;
 
-           let content = std::fs::read_to_string(content).unwrap();
+            run_blog(client, cli.output, command).await
+        }
+        Commands::Login(args) => {
+            let name = cli
+                .profile
+                .as_deref()
+                .unwrap_or(config::DEFAULT_PROFILE)
+                .to_string();
 
-           let response = reqwest.post(base.join("api/posts").unwrap())
-                .json(&CreatePostRequest { id: Uuid::new_v4(), title, content })
-                .header("X-Token", cli.token)
-                .send()
-                .await
-                .unwrap();
+            login(&directory, config, &name, args)
+        }
+        Commands::Profiles { command } => profiles(&directory, config, command),
+    }
+}
 
-            println!("{:?}", response);
-        },
+async fn run_blog(
+    client: Client,
+    output: Output,
+    command: BlogCommands,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        BlogCommands::Post { file } => {
+            let (post, created) = publish::publish(&client, &file, None, Uuid::new_v4()).await?;
+
+            eprintln!(
+                "{} {}",
+                if created { "Created" } else { "Updated" },
+                post.id
+            );
+            println!("{}", output.post(&post));
+        }
+        BlogCommands::Preview { file, listen } => preview::serve(client, file, listen).await?,
+        BlogCommands::Watch { path } => draft::watch(&client, &path).await?,
+        BlogCommands::Sync {
+            directory,
+            yes,
+            delete_all,
+        } => {
+            let local = sync::load(&directory)?;
+            let remote = client.list_posts(PostsQuery { deleted: false }).await?;
+            let changes = sync::plan(
+                &local,
+                &remote,
+                &client.list_posts(PostsQuery { deleted: true }).await?,
+            );
+            sync::check_deletes(&changes, &remote, delete_all)?;
+
+            if changes.is_empty() {
+                eprintln!("Nothing to sync");
+                return Ok(());
+            }
+
+            println!("{}", output.changes(&changes));
+
+            if !yes && !confirm(&format!("Apply {} changes?", changes.len()))? {
+                return Ok(());
+            }
+
+            sync::apply(&client, &directory, &changes).await?;
+        }
+        BlogCommands::List { deleted } => {
+            println!(
+                "{}",
+                output.posts(&client.list_posts(PostsQuery { deleted }).await?)
+            );
+        }
+        BlogCommands::Show { id } => println!("{}", output.post(&client.get_post(id).await?)),
+        BlogCommands::Edit { id } => {
+            let post = client.get_post(id).await?;
+            let edited = editor::edit(&post.content, &format!("{id}.md"))?;
+
+            if edited.text == post.content {
+                eprintln!("No changes");
+                edited.remove()?;
+                return Ok(());
+            }
+
+            let update = PostUpdateRequest {
+                content: Some(edited.text.clone()),
+                ..PostUpdateRequest::default()
+            };
+            let post = client.update_post(id, &update).await.inspect_err(|_| {
+                eprintln!("The changes are kept in {}", edited.path().display());
+            })?;
+
+            edited.remove()?;
+            println!("{}", output.post(&post));
+        }
+        BlogCommands::Update { id, title, content } => {
+            let update = PostUpdateRequest {
+                title,
+                content: content.map(std::fs::read_to_string).transpose()?,
+                ..PostUpdateRequest::default()
+            };
+
+            println!("{}", output.post(&client.update_post(id, &update).await?));
+        }
+        BlogCommands::Delete { id } => {
+            client.delete_post(id).await?;
+
+            eprintln!("Deleted {id}, `restore {id}` brings it back");
+        }
+        BlogCommands::Restore { id } => {
+            println!("{}", output.post(&client.restore_post(id).await?))
+        }
+        BlogCommands::Publish { id, at } => {
+            let update = PostUpdateRequest {
+                date_published: Some(at.unwrap_or_else(OffsetDateTime::now_utc)),
+                ..PostUpdateRequest::default()
+            };
+
+            println!("{}", output.post(&client.update_post(id, &update).await?));
+        }
     }
-    println!("Hello, world!");
+
+    Ok(())
 }
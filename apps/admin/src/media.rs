@@ -0,0 +1,369 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use blog_core::{
+    api::{media_path, media_sha256, media_type, MEDIA_TYPES},
+    client::{self, Client},
+    render::{code_block_lines, links},
+};
+use thiserror::Error;
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot read {0}: {1}")]
+    Read(String, io::Error),
+    #[error("The image {0} doesn't exist")]
+    MissingImage(String),
+    #[error("{0} is outside of {1}, only the files in it can be uploaded")]
+    OutsideDirectory(String, String),
+    #[error(
+        "Cannot rewrite the link to {0}, write it as a plain path, without escapes or entities"
+    )]
+    NotRewritten(String),
+    #[error("Cannot upload {0}, the blog only accepts {1} files")]
+    UnsupportedType(String, String),
+    #[error("{0} changed while it was uploaded, try again")]
+    Changed(String),
+    #[error("{0}")]
+    Client(#[from] client::Error),
+}
+
+/// A file next to the markdown that the post links to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalFile {
+    pub path: PathBuf,
+    pub content_type: &'static str,
+    pub sha256: String,
+}
+
+/// The markdown as it's sent to the blog, linking to the copies of the local files on the blog,
+/// and the files that have to be uploaded first.
+pub struct Prepared {
+    pub content: String,
+    pub files: Vec<LocalFile>,
+}
+
+/// The local file the URL points to, relative to `directory`, or `None` if it points elsewhere,
+/// like another site, a page of the blog, or an anchor. Only relative URLs are local, a `file:`
+/// one isn't either.
+pub fn resolve(directory: &Path, url: &str) -> Option<PathBuf> {
+    if url.is_empty() || url.starts_with(['/', '#', '?']) || Url::parse(url).is_ok() {
+        return None;
+    }
+
+    let mut url = Url::from_directory_path(directory).ok()?.join(url).ok()?;
+    url.set_query(None);
+    url.set_fragment(None);
+
+    url.to_file_path().ok()
+}
+
+fn blank(bytes: &mut [u8]) {
+    for x in bytes.iter_mut().filter(|x| **x != b'\n') {
+        *x = b' ';
+    }
+}
+
+fn backticks(text: &[u8], start: usize) -> usize {
+    text[start..].iter().take_while(|x| **x == b'`').count()
+}
+
+/// Whether the line starting after the newline at `position` is blank, a code span can't cross it.
+fn is_blank_line(text: &[u8], position: usize) -> bool {
+    text[position + 1..]
+        .iter()
+        .take_while(|x| **x != b'\n')
+        .all(u8::is_ascii_whitespace)
+}
+
+/// Blanks the code spans: they start and end with as many backticks, and a backslash escapes a
+/// backtick outside of them.
+fn blank_code_spans(text: &mut [u8]) {
+    let mut position = 0;
+
+    while position < text.len() {
+        match text[position] {
+            b'\\' => position += 2,
+            b'`' => {
+                let start = position;
+                let length = backticks(text, start);
+                position += length;
+
+                let mut end = position;
+                while end < text.len() {
+                    match text[end] {
+                        b'`' if backticks(text, end) == length => {
+                            blank(&mut text[start..end + length]);
+                            position = end + length;
+                            break;
+                        }
+                        b'`' => end += backticks(text, end),
+                        b'\n' if is_blank_line(text, end) => break,
+                        _ => end += 1,
+                    }
+                }
+            }
+            _ => position += 1,
+        }
+    }
+}
+
+/// The markdown with the code replaced with spaces, keeping the offsets: the code blocks and the
+/// HTML blocks, where the parser tells the lines, and the code spans.
+fn blank_code(markdown: &str) -> Vec<u8> {
+    let mut text = markdown.as_bytes().to_vec();
+    let code_lines = code_block_lines(markdown);
+
+    let mut offset = 0;
+    for (index, line) in markdown.split_inclusive('\n').enumerate() {
+        if code_lines.iter().any(|x| x.contains(&(index + 1))) {
+            blank(&mut text[offset..offset + line.len()]);
+        }
+        offset += line.len();
+    }
+
+    blank_code_spans(&mut text);
+
+    text
+}
+
+/// Replaces the link destinations, only where they're written as one: after `](`, `](<`, or the
+/// `]: ` of a reference, and not in code. The links written otherwise, like with escapes, are left
+/// as they are, see [`prepare`].
+fn rewrite(markdown: &str, replacements: &HashMap<String, String>) -> String {
+    let text = blank_code(markdown);
+    let mut result = String::with_capacity(markdown.len());
+    let mut position = 0;
+
+    while let Some(offset) = text[position..].iter().position(|x| *x == b']') {
+        let start = position + offset + 1;
+        result.push_str(&markdown[position..start]);
+        position = start;
+
+        let Some(prefix) = ["(<", "(", ": "]
+            .into_iter()
+            .find(|x| text[start..].starts_with(x.as_bytes()))
+        else {
+            continue;
+        };
+        let destination = start + prefix.len();
+
+        let replacement = replacements.iter().find(|(from, _)| {
+            // Not in the code either, where the text is blank
+            markdown[destination..].starts_with(from.as_str())
+                && text[destination..].starts_with(from.as_bytes())
+                && text[destination + from.len()..]
+                    .first()
+                    .is_none_or(|x| matches!(x, b')' | b'>' | b' ' | b'\t' | b'\r' | b'\n'))
+        });
+        if let Some((from, to)) = replacement {
+            result.push_str(prefix);
+            result.push_str(to);
+            position = destination + from.len();
+        }
+    }
+
+    result.push_str(&markdown[position..]);
+    result
+}
+
+/// Finds the local files the markdown of the file at `path` links to, and replaces the links with
+/// the URLs of the files on the blog. The files are addressed by their content, so the URLs are
+/// known before they're uploaded, and the same markdown is sent every time the files are the same.
+///
+/// The files have to be in `root`, the directory being published, or the one of the markdown if
+/// `None`, following the symlinks, so that a post can't upload any file that can be read.
+pub fn prepare(markdown: &str, path: &Path, root: Option<&Path>) -> Result<Prepared, Error> {
+    let canonicalize = |path: &Path| {
+        std::fs::canonicalize(path).map_err(|e| Error::Read(path.display().to_string(), e))
+    };
+    let path = canonicalize(path)?;
+    let directory = path.parent().unwrap_or(Path::new("/"));
+    let root = match root {
+        Some(root) => canonicalize(root)?,
+        None => directory.to_path_buf(),
+    };
+
+    let mut files: Vec<LocalFile> = vec![];
+    let mut replacements: HashMap<String, String> = HashMap::new();
+
+    for link in links(markdown) {
+        if replacements.contains_key(&link.url) {
+            continue;
+        }
+        let Some(file) = resolve(directory, &link.url) else {
+            continue;
+        };
+        let Some(file) = std::fs::canonicalize(file).ok().filter(|x| x.is_file()) else {
+            // A link can be to a page of the blog, an image has to be a file
+            if link.image {
+                return Err(Error::MissingImage(link.url));
+            }
+            continue;
+        };
+        if !file.starts_with(&root) {
+            return Err(Error::OutsideDirectory(
+                link.url,
+                root.display().to_string(),
+            ));
+        }
+
+        let content_type = file
+            .extension()
+            .and_then(|x| x.to_str())
+            .and_then(media_type)
+            .ok_or_else(|| {
+                let extensions: Vec<&str> = MEDIA_TYPES.iter().map(|(x, _)| *x).collect();
+                Error::UnsupportedType(link.url.clone(), extensions.join(", "))
+            })?;
+        let content =
+            std::fs::read(&file).map_err(|e| Error::Read(file.display().to_string(), e))?;
+        let sha256 = media_sha256(&content);
+
+        let fragment = link
+            .url
+            .split_once('#')
+            .map_or_else(String::new, |(_, x)| format!("#{x}"));
+        replacements.insert(link.url, format!("{}{fragment}", media_path(&sha256)));
+
+        if !files.iter().any(|x| x.sha256 == sha256) {
+            files.push(LocalFile {
+                path: file,
+                content_type,
+                sha256,
+            });
+        }
+    }
+
+    let content = rewrite(markdown, &replacements);
+    // The links that weren't found in the text would point to files that aren't on the blog
+    if let Some(link) = links(&content)
+        .into_iter()
+        .find(|x| replacements.contains_key(&x.url))
+    {
+        return Err(Error::NotRewritten(link.url));
+    }
+
+    Ok(Prepared { content, files })
+}
+
+/// Uploads the files the blog doesn't have yet.
+pub async fn upload(client: &Client, files: &[LocalFile]) -> Result<(), Error> {
+    for file in files {
+        if client.has_media(&file.sha256).await? {
+            continue;
+        }
+
+        let content = std::fs::read(&file.path)
+            .map_err(|e| Error::Read(file.path.display().to_string(), e))?;
+        // The markdown links to the content that was hashed
+        if media_sha256(&content) != file.sha256 {
+            return Err(Error::Changed(file.path.display().to_string()));
+        }
+
+        client.put_media(file.content_type, content).await?;
+        eprintln!("Uploaded {}", file.path.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn resolves_local_files() {
+        let directory = Path::new("/posts");
+
+        assert_eq!(
+            Some(PathBuf::from("/posts/diagram.png")),
+            resolve(directory, "./diagram.png")
+        );
+        assert_eq!(
+            Some(PathBuf::from("/images/a b.png")),
+            resolve(directory, "../images/a%20b.png")
+        );
+        assert_eq!(
+            Some(PathBuf::from("/posts/paper.pdf")),
+            resolve(directory, "paper.pdf#page=2")
+        );
+        assert_eq!(None, resolve(directory, "https://example.com/a.png"));
+        assert_eq!(None, resolve(directory, "mailto:me@example.com"));
+        assert_eq!(None, resolve(directory, "file:///etc/passwd"));
+        assert_eq!(None, resolve(directory, "/posts/hello"));
+        assert_eq!(None, resolve(directory, "#heading"));
+    }
+
+    #[test]
+    pub fn rewrites_the_links() {
+        let replacements = HashMap::from([
+            ("./a.png".to_string(), "/media/a".to_string()),
+            ("b.pdf".to_string(), "/media/b".to_string()),
+        ]);
+
+        assert_eq!(
+            "![A](/media/a \"Title\") [B](/media/b) [C](b.pdf.txt) ![](</media/a>)\n\n\
+                Not a link: ./a.png\n\n[b]: /media/b\n",
+            rewrite(
+                "![A](./a.png \"Title\") [B](b.pdf) [C](b.pdf.txt) ![](<./a.png>)\n\n\
+                    Not a link: ./a.png\n\n[b]: b.pdf\n",
+                &replacements
+            )
+        );
+
+        let code =
+            "`[A](./a.png)` ``a ` [A](./a.png)``\n\n```\n[B](b.pdf)\n```\n\n    [b]: b.pdf\n";
+        assert_eq!(code, rewrite(code, &replacements));
+        assert_eq!(
+            "\\`[A](/media/a)`",
+            rewrite("\\`[A](./a.png)`", &replacements)
+        );
+    }
+
+    #[test]
+    pub fn fails_on_the_links_it_cannot_rewrite() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("a_b.png"), "png").unwrap();
+        let post = directory.path().join("post.md");
+        std::fs::write(&post, "").unwrap();
+
+        assert!(prepare("![A](a_b.png)", &post, None).is_ok());
+        assert!(matches!(
+            prepare("![A](a\\_b.png)", &post, None),
+            Err(Error::NotRewritten(..))
+        ));
+        assert!(matches!(
+            prepare("![A](a&#95;b.png)", &post, None),
+            Err(Error::NotRewritten(..))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    pub fn uploads_only_the_files_in_the_directory() {
+        let (root, elsewhere) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let posts = root.path().join("posts");
+        std::fs::create_dir(&posts).unwrap();
+        std::fs::write(root.path().join("a.png"), "png").unwrap();
+        std::fs::write(elsewhere.path().join("b.png"), "png").unwrap();
+        std::os::unix::fs::symlink(elsewhere.path().join("b.png"), posts.join("b.png")).unwrap();
+        let post = posts.join("post.md");
+        std::fs::write(&post, "").unwrap();
+
+        let prepared = prepare("![A](../a.png)", &post, Some(root.path())).unwrap();
+        assert_eq!(1, prepared.files.len());
+        assert!(matches!(
+            prepare("![A](../a.png)", &post, None),
+            Err(Error::OutsideDirectory(..))
+        ));
+        assert!(matches!(
+            prepare("![B](b.png)", &post, Some(root.path())),
+            Err(Error::OutsideDirectory(..))
+        ));
+    }
+}
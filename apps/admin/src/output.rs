@@ -0,0 +1,180 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime, UtcOffset};
+
+use blog_core::api::{PostResponse, PostSummaryResponse};
+
+use crate::sync::Change;
+
+const DATE_FORMAT: &[FormatItem<'_>] = format_description!("[year]-[month]-[day] [hour]:[minute]");
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Output {
+    /// Aligned columns, for reading
+    #[default]
+    Table,
+    /// The API responses as they are, for scripts
+    Json,
+}
+
+fn format_date(date: OffsetDateTime) -> String {
+    date.to_offset(UtcOffset::UTC)
+        .format(DATE_FORMAT)
+        .unwrap_or_default()
+}
+
+const fn status(published: bool, deleted_at: Option<OffsetDateTime>) -> &'static str {
+    match (published, deleted_at) {
+        (_, Some(_)) => "deleted",
+        (true, None) => "published",
+        (false, None) => "scheduled",
+    }
+}
+
+/// Pads every column to its widest value, the last one is not padded.
+fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|x| x.chars().count()).collect();
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(ToString::to_string).collect();
+
+    std::iter::once(&header)
+        .chain(rows)
+        .map(|row| {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{value:width$}"))
+                .collect();
+
+            line.join("  ").trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn json(value: &impl Serialize) -> String {
+    serde_json::to_string_pretty(value).expect("The API responses can be serialized")
+}
+
+impl Output {
+    pub fn posts(self, posts: &[PostSummaryResponse]) -> String {
+        match self {
+            Self::Json => json(&posts),
+            Self::Table => {
+                let rows: Vec<Vec<String>> = posts
+                    .iter()
+                    .map(|x| {
+                        vec![
+                            x.id.to_string(),
+                            format_date(x.date_published),
+                            status(x.published, x.deleted_at).to_string(),
+                            x.title.clone(),
+                        ]
+                    })
+                    .collect();
+
+                table(&["ID", "PUBLISHED (UTC)", "STATUS", "TITLE"], &rows)
+            }
+        }
+    }
+
+    pub fn changes(self, changes: &[Change]) -> String {
+        match self {
+            Self::Json => json(&changes),
+            Self::Table => {
+                let rows: Vec<Vec<String>> = changes
+                    .iter()
+                    .map(|x| match x {
+                        Change::Create { path, id, title } => {
+                            vec![
+                                "create".to_string(),
+                                id.to_string(),
+                                path.display().to_string(),
+                                title.clone(),
+                            ]
+                        }
+                        Change::Update {
+                            path,
+                            id,
+                            title,
+                            fields,
+                        } => vec![
+                            format!("update ({})", fields.join(", ")),
+                            id.to_string(),
+                            path.display().to_string(),
+                            title.clone(),
+                        ],
+                        Change::Restore { path, id, title } => {
+                            vec![
+                                "restore".to_string(),
+                                id.to_string(),
+                                path.display().to_string(),
+                                title.clone(),
+                            ]
+                        }
+                        Change::Delete { id, title } => {
+                            vec![
+                                "delete".to_string(),
+                                id.to_string(),
+                                String::new(),
+                                title.clone(),
+                            ]
+                        }
+                    })
+                    .collect();
+
+                table(&["ACTION", "ID", "FILE", "TITLE"], &rows)
+            }
+        }
+    }
+
+    pub fn post(self, post: &PostResponse) -> String {
+        match self {
+            Self::Json => json(post),
+            Self::Table => format!(
+                "{}\n\n{}",
+                table(
+                    &["ID", "PUBLISHED (UTC)", "STATUS", "TITLE"],
+                    &[vec![
+                        post.id.to_string(),
+                        format_date(post.date_published),
+                        status(post.published, None).to_string(),
+                        post.title.clone(),
+                    ]],
+                ),
+                post.content.trim_end()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn aligns_the_columns() {
+        let rows = vec![
+            vec![
+                "1".to_string(),
+                "published".to_string(),
+                "First".to_string(),
+            ],
+            vec![
+                "22".to_string(),
+                "scheduled".to_string(),
+                "Second".to_string(),
+            ],
+        ];
+
+        assert_eq!(
+            "ID  STATUS     TITLE\n1   published  First\n22  scheduled  Second",
+            table(&["ID", "STATUS", "TITLE"], &rows)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode, Uri},
+    response::{
+        sse::{Event, KeepAlive},
+        Html, IntoResponse, Response, Sse,
+    },
+    routing::get,
+    Router,
+};
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use url::Url;
+
+use blog_core::{
+    api::{media_type, PreviewRequest},
+    client::Client,
+};
+
+use crate::{document::Document, media, watch::Watcher};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot listen on {0}: {1}")]
+    Listen(SocketAddr, hyper::Error),
+    #[error("The server failed: {0}")]
+    Server(#[from] hyper::Error),
+}
+
+/// Reloads the page once the file changes, the browser reconnects by itself if the connection
+/// drops.
+const RELOAD_SCRIPT: &str =
+    r#"<script>new EventSource("/_reload").onmessage = () => location.reload();</script>"#;
+
+struct PreviewState {
+    client: Client,
+    path: PathBuf,
+    reloads: broadcast::Sender<()>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The page as the blog renders it, or the error, so that it's visible without leaving the browser.
+async fn render(client: &Client, path: &Path) -> String {
+    let page = match Document::load(path) {
+        Ok(document) => match document.title() {
+            Ok(title) => client
+                .preview(&PreviewRequest {
+                    title: title.to_string(),
+                    content: document.content.clone(),
+                    description: document.front_matter.description.clone(),
+                })
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        },
+        Err(e) => Err(e.to_string()),
+    };
+
+    let page = page.unwrap_or_else(|e| {
+        format!(
+            "<!DOCTYPE html><html><body><pre>{}</pre></body></html>",
+            escape(&e)
+        )
+    });
+
+    match page.rfind("</body>") {
+        Some(end) => format!("{}{RELOAD_SCRIPT}{}", &page[..end], &page[end..]),
+        None => format!("{page}{RELOAD_SCRIPT}"),
+    }
+}
+
+async fn route_page(State(state): State<Arc<PreviewState>>) -> Html<String> {
+    Html(render(&state.client, &state.path).await)
+}
+
+async fn route_reload(
+    State(state): State<Arc<PreviewState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let reloads = BroadcastStream::new(state.reloads.subscribe())
+        .map(|_| Ok(Event::default().data("reload")));
+
+    Sse::new(reloads).keep_alive(KeepAlive::default())
+}
+
+/// A file next to the markdown that it links to, the blog only has it once it's published.
+fn local_file(markdown: &Path, path: &str) -> Option<(&'static str, Vec<u8>)> {
+    let markdown = std::fs::canonicalize(markdown).ok()?;
+    let directory = markdown.parent()?;
+    // Following the symlinks, so that they can't point outside of the directory
+    let file = std::fs::canonicalize(media::resolve(directory, path)?).ok()?;
+    if !file.starts_with(directory) {
+        return None;
+    }
+
+    let content_type = media_type(file.extension()?.to_str()?)?;
+
+    Some((content_type, std::fs::read(file).ok()?))
+}
+
+/// Whether the path is one of the blog's. A URL, like in `/http://169.254.169.254/`, would make the
+/// preview fetch it for any page open in the browser.
+fn is_blog_path(path: &str) -> bool {
+    Url::parse(path).is_err() && !path.starts_with('\\')
+}
+
+/// Everything else, like the CSS and the fonts, comes from the blog, unless it's a local file.
+async fn route_asset(State(state): State<Arc<PreviewState>>, uri: Uri) -> Response {
+    let path = uri
+        .path_and_query()
+        .map_or("", |x| x.as_str())
+        .trim_start_matches('/');
+
+    if !is_blog_path(path) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    if let Some((content_type, content)) = local_file(&state.path, path) {
+        return ([(header::CONTENT_TYPE, content_type)], content).into_response();
+    }
+
+    let response = match state.client.asset(path).await {
+        Ok(response) => response,
+        Err(e) => return (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    };
+
+    let status = StatusCode::from_u16(response.status().as_u16())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+
+    match response.bytes().await {
+        Ok(body) => (status, [(header::CONTENT_TYPE, content_type)], body).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    }
+}
+
+/// Serves the file rendered by the blog on `address`, reloading the page whenever it's saved.
+pub async fn serve(client: Client, path: PathBuf, address: SocketAddr) -> Result<(), Error> {
+    let (reloads, _) = broadcast::channel(16);
+    let mut watcher = Watcher::new(&path);
+    let state = Arc::new(PreviewState {
+        client,
+        path,
+        reloads,
+    });
+
+    let router = Router::new()
+        .route("/", get(route_page))
+        .route("/_reload", get(route_reload))
+        .fallback(route_asset)
+        .with_state(state.clone());
+
+    let server = axum::Server::try_bind(&address)
+        .map_err(|e| Error::Listen(address, e))?
+        .serve(router.into_make_service());
+
+    eprintln!(
+        "Previewing {} at http://{}/",
+        state.path.display(),
+        server.local_addr()
+    );
+
+    let reload = async {
+        loop {
+            watcher.changed().await;
+
+            eprintln!("{} changed, reloading", state.path.display());
+            // Nobody to tell if no page is open
+            let _ = state.reloads.send(());
+        }
+    };
+
+    tokio::select! {
+        result = server => Ok(result?),
+        () = reload => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn proxies_only_the_blog() {
+        assert!(is_blog_path("static/style.css"));
+        assert!(is_blog_path("media/abc?v=1"));
+        assert!(!is_blog_path("http://169.254.169.254/latest"));
+        assert!(!is_blog_path("file:///etc/passwd"));
+        assert!(!is_blog_path("\\\\example.com/a"));
+    }
+}
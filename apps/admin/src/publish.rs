@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use blog_core::{
+    api::{PostPutRequest, PostResponse},
+    client::{self, Client},
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    document::{self, Document},
+    media::{self, prepare, upload},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Document(#[from] document::Error),
+    #[error("{0}")]
+    Media(#[from] media::Error),
+    #[error("{0}")]
+    Client(#[from] client::Error),
+}
+
+/// Publishes the markdown file, creating the post or replacing the one with the ID from the front
+/// matter. The ID of a new post is saved into the file before it's created, so that the post is
+/// never created twice, even if the request fails midway. The local files it links to are uploaded
+/// first, they have to be in `root`, see [`prepare`]. `new_id` is the ID of the post if the file
+/// has none. Returns `true` if the post was created.
+pub async fn publish(
+    client: &Client,
+    path: &Path,
+    root: Option<&Path>,
+    new_id: Uuid,
+) -> Result<(PostResponse, bool), Error> {
+    let mut document = Document::load(path)?;
+    let title = document.title()?.to_string();
+    let id = document.ensure_id(path, new_id)?;
+    let prepared = prepare(&document.content, path, root)?;
+    upload(client, &prepared.files).await?;
+
+    let front_matter = document.front_matter;
+    let post = PostPutRequest {
+        title,
+        content: prepared.content,
+        date_published: front_matter.date,
+        slug: front_matter.slug,
+        description: front_matter.description,
+        tags: front_matter.tags,
+    };
+
+    Ok(client.put_post(id, &post).await?)
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use thiserror::Error;
+use uuid::{uuid, Uuid};
+
+use blog_core::{
+    api::{content_sha256, PostResponse, PostSummaryResponse},
+    client::{self, Client},
+};
+
+use crate::{
+    document::{self, Document},
+    media::{self, prepare},
+    publish::{self, publish},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot read the directory {0}: {1}")]
+    Directory(String, io::Error),
+    #[error("{0}: {1}")]
+    Document(String, document::Error),
+    #[error("{0}: {1}")]
+    Media(String, media::Error),
+    #[error("{1} and {2} have the same ID {0}")]
+    DuplicateId(Uuid, String, String),
+    #[error("The directory has none of the {0} posts on the blog, pass --delete-all to delete all of them")]
+    DeletesEverything(usize),
+    #[error("{0}: {1}")]
+    Publish(String, publish::Error),
+    #[error("{0}")]
+    Client(#[from] client::Error),
+}
+
+/// The namespace of the IDs derived from the paths, see [`path_id`].
+const PATH_NAMESPACE: Uuid = uuid!("5b0f8a36-7d4e-4c52-9a0e-2f6c1d8b93a7");
+
+/// A markdown file from the directory.
+pub struct LocalPost {
+    path: PathBuf,
+    /// From the front matter, or derived from the path
+    id: Uuid,
+    document: Document,
+}
+
+/// What has to be done for the blog to match the directory.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Change {
+    /// The ID is written into the file when the post is created, if it's not there yet
+    Create {
+        path: PathBuf,
+        id: Uuid,
+        title: String,
+    },
+    Update {
+        path: PathBuf,
+        id: Uuid,
+        title: String,
+        /// The names of the fields that differ
+        fields: Vec<&'static str>,
+    },
+    /// The post was deleted on the server, but the file is still there
+    Restore {
+        path: PathBuf,
+        id: Uuid,
+        title: String,
+    },
+    /// There's no file for the post
+    Delete { id: Uuid, title: String },
+}
+
+/// The ID of a file that has none in its front matter, the same for every sync of the directory,
+/// even where the ID written into the file is not kept, like in CI.
+fn path_id(relative_path: &Path) -> Uuid {
+    let name: Vec<String> = relative_path
+        .components()
+        .map(|x| x.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    Uuid::new_v5(&PATH_NAMESPACE, name.join("/").as_bytes())
+}
+
+/// Reads all the posts in the directory, all of them have to be valid for anything to be synced.
+pub fn load(directory: &Path) -> Result<Vec<LocalPost>, Error> {
+    let files = document::find_all(directory)
+        .map_err(|e| Error::Directory(directory.display().to_string(), e))?;
+
+    let mut posts: Vec<LocalPost> = vec![];
+    let mut ids: HashMap<Uuid, usize> = HashMap::new();
+
+    for path in files {
+        let mut document =
+            Document::load(&path).map_err(|e| Error::Document(path.display().to_string(), e))?;
+        if let Err(e) = document.title() {
+            return Err(Error::Document(path.display().to_string(), e));
+        }
+
+        // Compared with the content on the blog, which links to the uploaded files
+        document.content = prepare(&document.content, &path, Some(directory))
+            .map_err(|e| Error::Media(path.display().to_string(), e))?
+            .content;
+
+        let id = document
+            .front_matter
+            .id
+            .unwrap_or_else(|| path_id(path.strip_prefix(directory).unwrap_or(&path)));
+        if let Some(&other) = ids.get(&id) {
+            return Err(Error::DuplicateId(
+                id,
+                posts[other].path.display().to_string(),
+                path.display().to_string(),
+            ));
+        }
+        ids.insert(id, posts.len());
+
+        posts.push(LocalPost { path, id, document });
+    }
+
+    Ok(posts)
+}
+
+fn changed_fields(document: &Document, remote: &PostSummaryResponse) -> Vec<&'static str> {
+    let front_matter = &document.front_matter;
+
+    [
+        ("title", front_matter.title != Some(remote.title.clone())),
+        (
+            "content",
+            content_sha256(&document.content) != remote.content_sha256,
+        ),
+        ("slug", front_matter.slug != remote.slug),
+        (
+            "description",
+            front_matter.description != remote.description,
+        ),
+        ("tags", front_matter.tags != remote.tags),
+        // Without a date in the file, the post keeps the one it was published with
+        (
+            "date",
+            front_matter
+                .date
+                .is_some_and(|x| x != remote.date_published),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, changed)| changed.then_some(name))
+    .collect()
+}
+
+/// Compares the files with the posts on the server, `deleted` are the deleted ones.
+pub fn plan(
+    local: &[LocalPost],
+    remote: &[PostSummaryResponse],
+    deleted: &[PostSummaryResponse],
+) -> Vec<Change> {
+    let remote_by_id: HashMap<Uuid, &PostSummaryResponse> =
+        remote.iter().map(|x| (x.id, x)).collect();
+    let deleted_ids: HashSet<Uuid> = deleted.iter().map(|x| x.id).collect();
+    let mut changes = vec![];
+
+    for post in local {
+        let path = post.path.clone();
+        let title = post.document.front_matter.title.clone().unwrap_or_default();
+        let id = post.id;
+
+        match remote_by_id.get(&id) {
+            Some(remote) => {
+                let fields = changed_fields(&post.document, remote);
+                if !fields.is_empty() {
+                    changes.push(Change::Update {
+                        path,
+                        id,
+                        title,
+                        fields,
+                    });
+                }
+            }
+            None if deleted_ids.contains(&id) => changes.push(Change::Restore { path, id, title }),
+            None => changes.push(Change::Create { path, id, title }),
+        }
+    }
+
+    let local_ids: HashSet<Uuid> = local.iter().map(|x| x.id).collect();
+    changes.extend(
+        remote
+            .iter()
+            .filter(|x| !local_ids.contains(&x.id))
+            .map(|x| Change::Delete {
+                id: x.id,
+                title: x.title.clone(),
+            }),
+    );
+
+    changes
+}
+
+/// Guards against syncing the wrong directory, like an empty one or a mistyped path, which would
+/// delete every post.
+pub fn check_deletes(
+    changes: &[Change],
+    remote: &[PostSummaryResponse],
+    delete_all: bool,
+) -> Result<(), Error> {
+    let deletes = changes
+        .iter()
+        .filter(|x| matches!(x, Change::Delete { .. }))
+        .count();
+
+    if !delete_all && !remote.is_empty() && deletes == remote.len() {
+        return Err(Error::DeletesEverything(remote.len()));
+    }
+
+    Ok(())
+}
+
+async fn publish_file(
+    client: &Client,
+    directory: &Path,
+    path: &Path,
+    id: Uuid,
+) -> Result<(PostResponse, bool), Error> {
+    publish(client, path, Some(directory), id)
+        .await
+        .map_err(|e| Error::Publish(path.display().to_string(), e))
+}
+
+/// Applies the changes one by one, stopping at the first failure. Running the sync again
+/// continues from there, as every change is idempotent.
+pub async fn apply(client: &Client, directory: &Path, changes: &[Change]) -> Result<(), Error> {
+    for change in changes {
+        match change {
+            Change::Create { path, id, .. } => {
+                let (post, _) = publish_file(client, directory, path, *id).await?;
+                eprintln!("Created {} from {}", post.id, path.display());
+            }
+            Change::Update { path, id, .. } => {
+                publish_file(client, directory, path, *id).await?;
+                eprintln!("Updated {id} from {}", path.display());
+            }
+            Change::Restore { path, id, .. } => {
+                client.restore_post(*id).await?;
+                publish_file(client, directory, path, *id).await?;
+                eprintln!("Restored {id} from {}", path.display());
+            }
+            Change::Delete { id, .. } => {
+                client.delete_post(*id).await?;
+                eprintln!("Deleted {id}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn local(path: &str, id: Option<Uuid>, content: &str) -> LocalPost {
+        let front_matter = id.map_or_else(String::new, |x| format!("id: {x}\n"));
+
+        LocalPost {
+            path: PathBuf::from(path),
+            id: id.unwrap_or_else(|| path_id(Path::new(path))),
+            document: Document::parse(format!("---\n{front_matter}title: Post\n---\n{content}"))
+                .unwrap(),
+        }
+    }
+
+    fn remote(id: Uuid, content: &str) -> PostSummaryResponse {
+        PostSummaryResponse {
+            id,
+            title: "Post".to_string(),
+            date_published: datetime!(2026-10-19 10:00 UTC),
+            published: true,
+            slug: None,
+            description: None,
+            tags: vec![],
+            content_sha256: content_sha256(content),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    pub fn plans_the_changes() {
+        let (unchanged, changed, deleted, removed) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+
+        let changes = plan(
+            &[
+                local("new.md", None, "New"),
+                local("unchanged.md", Some(unchanged), "Text"),
+                local("changed.md", Some(changed), "Changed"),
+                local("deleted.md", Some(deleted), "Text"),
+            ],
+            &[
+                remote(unchanged, "Text"),
+                remote(changed, "Text"),
+                remote(removed, "Text"),
+            ],
+            &[remote(deleted, "Text")],
+        );
+
+        assert_eq!(
+            vec![
+                Change::Create {
+                    path: PathBuf::from("new.md"),
+                    id: path_id(Path::new("new.md")),
+                    title: "Post".to_string()
+                },
+                Change::Update {
+                    path: PathBuf::from("changed.md"),
+                    id: changed,
+                    title: "Post".to_string(),
+                    fields: vec!["content"]
+                },
+                Change::Restore {
+                    path: PathBuf::from("deleted.md"),
+                    id: deleted,
+                    title: "Post".to_string()
+                },
+                Change::Delete {
+                    id: removed,
+                    title: "Post".to_string()
+                },
+            ],
+            changes
+        );
+    }
+
+    #[test]
+    pub fn files_without_an_id_keep_their_post() {
+        let new = path_id(Path::new("posts/new.md"));
+
+        assert_eq!(new, path_id(Path::new("posts/new.md")));
+        assert_ne!(new, path_id(Path::new("posts/other.md")));
+        assert!(plan(
+            &[local("posts/new.md", None, "New")],
+            &[remote(new, "New")],
+            &[]
+        )
+        .is_empty());
+    }
+
+    #[test]
+    pub fn refuses_to_delete_every_post() {
+        let remote = [remote(Uuid::new_v4(), "Text")];
+        let changes = plan(&[], &remote, &[]);
+
+        assert!(matches!(
+            check_deletes(&changes, &remote, false),
+            Err(Error::DeletesEverything(1))
+        ));
+        assert!(check_deletes(&changes, &remote, true).is_ok());
+        assert!(check_deletes(&[], &[], false).is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::document;
+
+/// How often the modification times are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The modification time of the file, or of every markdown file in the directory.
+fn snapshot(path: &Path) -> BTreeMap<PathBuf, SystemTime> {
+    let files = if path.is_dir() {
+        document::find_all(path).unwrap_or_default()
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    files
+        .into_iter()
+        .filter_map(|x| {
+            let modified = std::fs::metadata(&x).and_then(|x| x.modified()).ok()?;
+
+            Some((x, modified))
+        })
+        .collect()
+}
+
+/// Polls the modification times, which works the same everywhere, including editors that replace
+/// the file instead of writing to it.
+pub struct Watcher {
+    path: PathBuf,
+    files: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl Watcher {
+    /// `path` is a file, or a directory of markdown files, including the ones added later.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            files: snapshot(path),
+        }
+    }
+
+    /// The files being watched.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+
+    /// Waits for files to change or to be added, and then to stop changing, as editors often save
+    /// in steps. Returns the files that changed.
+    pub async fn changed(&mut self) -> Vec<PathBuf> {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let mut current = snapshot(&self.path);
+            if current == self.files {
+                continue;
+            }
+
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+
+                let next = snapshot(&self.path);
+                if next == current {
+                    break;
+                }
+                current = next;
+            }
+
+            let changed: Vec<PathBuf> = current
+                .iter()
+                .filter(|(path, modified)| self.files.get(*path) != Some(modified))
+                .map(|(path, _)| path.clone())
+                .collect();
+            self.files = current;
+
+            // Only deletions, there's nothing to do about them
+            if !changed.is_empty() {
+                return changed;
+            }
+        }
+    }
+}
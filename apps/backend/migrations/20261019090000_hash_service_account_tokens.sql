@@ -0,0 +1,14 @@
+ALTER TABLE service_account_tokens
+    ADD COLUMN prefix TEXT,
+    ADD COLUMN secret_hash BYTEA;
+
+-- The existing tokens have no separator, so their first 16 characters become the prefix
+UPDATE service_account_tokens SET
+    prefix = substring(content FROM 1 FOR 16),
+    secret_hash = sha256(convert_to(substring(content FROM 17), 'UTF8'));
+
+ALTER TABLE service_account_tokens
+    ALTER COLUMN prefix SET NOT NULL,
+    ALTER COLUMN secret_hash SET NOT NULL,
+    ADD CONSTRAINT service_account_tokens_prefix_key UNIQUE (prefix),
+    DROP COLUMN content;
\ No newline at end of file
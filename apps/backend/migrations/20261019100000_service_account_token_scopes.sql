@@ -0,0 +1,4 @@
+ALTER TABLE service_account_tokens ADD COLUMN scopes TEXT[] NOT NULL DEFAULT '{}';
+
+-- The tokens created so far could do everything, keep it that way
+UPDATE service_account_tokens SET scopes = ARRAY['posts:read', 'posts:write', 'posts:delete', 'accounts:admin'];
\ No newline at end of file
@@ -0,0 +1,4 @@
+ALTER TABLE service_account_tokens
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN expires_at TIMESTAMPTZ,
+    ADD COLUMN last_used_at TIMESTAMPTZ;
\ No newline at end of file
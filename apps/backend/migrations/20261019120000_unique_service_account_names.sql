@@ -0,0 +1 @@
+ALTER TABLE service_accounts ADD CONSTRAINT service_accounts_name_key UNIQUE (name);
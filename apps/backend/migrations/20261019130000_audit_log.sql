@@ -0,0 +1,23 @@
+-- There's no foreign key to the service accounts, the entries have to outlive them
+CREATE TABLE audit_log (
+    id UUID PRIMARY KEY,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    service_account UUID NOT NULL,
+    token UUID NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    request_id TEXT,
+    trace_id TEXT,
+    client_ip TEXT,
+    status_code INTEGER NOT NULL,
+    outcome TEXT NOT NULL
+);
+
+CREATE INDEX audit_log_occurred_at ON audit_log (occurred_at);
+CREATE INDEX audit_log_service_account ON audit_log (service_account, occurred_at);
+CREATE INDEX audit_log_action ON audit_log (action, occurred_at);
+
+-- Entries can be added and read, but never changed
+GRANT INSERT, SELECT
+ON TABLE audit_log
+TO app;
@@ -0,0 +1,2 @@
+-- Incremented on every save, so that concurrent changes to an account can be detected
+ALTER TABLE service_accounts ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
@@ -0,0 +1,11 @@
+-- The nonces of signed requests, kept only until the requests' timestamps expire
+CREATE TABLE request_nonces (
+    key_id TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (key_id, nonce)
+);
+
+GRANT INSERT, UPDATE, SELECT, DELETE
+ON TABLE request_nonces
+TO app;
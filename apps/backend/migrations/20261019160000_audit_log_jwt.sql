@@ -0,0 +1,2 @@
+-- Requests authenticated with a JWT have no token
+ALTER TABLE audit_log ALTER COLUMN token DROP NOT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE posts
+    ADD COLUMN slug TEXT UNIQUE,
+    ADD COLUMN description TEXT,
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,15 @@
+-- Unsaved versions of posts, pushed by `admin watch` while writing. The ID is the one the post has
+-- or will have once published, so there's no foreign key.
+CREATE TABLE post_drafts (
+    id UUID PRIMARY KEY,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    slug TEXT,
+    description TEXT,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+GRANT INSERT, UPDATE, SELECT, DELETE
+ON TABLE post_drafts
+TO app;
@@ -0,0 +1,12 @@
+-- Images and other files the posts link to, addressed by the SHA-256 of the content, so the same
+-- file is only stored once. They're never changed, a changed file is a new one.
+CREATE TABLE media (
+    sha256 TEXT PRIMARY KEY,
+    content_type TEXT NOT NULL,
+    content BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+GRANT INSERT, SELECT
+ON TABLE media
+TO app;
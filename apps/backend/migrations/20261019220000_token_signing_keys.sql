@@ -0,0 +1,4 @@
+-- The keys of the signed requests, derived from the secrets and masked with a pepper that's not in
+-- the database, so that a copy of this table isn't enough to sign requests. The tokens issued
+-- before have no key, they have to be rotated before they can sign requests.
+ALTER TABLE service_account_tokens ADD COLUMN signing_key BYTEA;
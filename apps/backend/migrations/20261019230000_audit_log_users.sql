@@ -0,0 +1,6 @@
+-- The web editor's actions are recorded too, made by a user instead of a service account
+ALTER TABLE audit_log ALTER COLUMN service_account DROP NOT NULL;
+ALTER TABLE audit_log ADD COLUMN user_id UUID;
+ALTER TABLE audit_log ADD CONSTRAINT audit_log_actor CHECK ((service_account IS NULL) <> (user_id IS NULL));
+
+CREATE INDEX audit_log_user ON audit_log (user_id, occurred_at);
@@ -0,0 +1,3 @@
+-- The scopes a JWT naming the account can be granted, the claims of the JWT can only narrow them
+-- down. Empty by default, so that the identity provider can't grant anything until it's allowed.
+ALTER TABLE service_accounts ADD COLUMN jwt_scopes TEXT[] NOT NULL DEFAULT '{}';
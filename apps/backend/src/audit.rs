@@ -0,0 +1,271 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::QueryRejection, MatchedPath, OriginalUri, Query, State},
+    http::{Method, Request},
+    middleware::Next,
+    response::Response,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use time::OffsetDateTime;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    client_ip::ClientIp,
+    error::ApiError,
+    service_accounts::{self, Authenticated, Scope},
+    users::AuthenticatedUser,
+};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// The resource an action was about, for when it's not the one in the path (e.g. a post that was
+/// just created). Handlers can return it as a response extension.
+#[derive(Debug, Clone)]
+pub struct AuditTarget(pub String);
+
+/// Returned as a response extension by the handlers that don't change anything despite the method,
+/// like the previews, so that they aren't recorded.
+#[derive(Debug, Clone, Copy)]
+pub struct NotAudited;
+
+#[derive(Serialize)]
+pub struct Entry {
+    id: Uuid,
+    #[serde(with = "time::serde::rfc3339")]
+    occurred_at: OffsetDateTime,
+    /// `None` for the actions of users in the web editor
+    service_account_id: Option<Uuid>,
+    /// `None` for requests authenticated with a JWT, and for users
+    token_id: Option<Uuid>,
+    /// Set only for the actions of users in the web editor
+    user_id: Option<Uuid>,
+    /// The method and the route, e.g. `POST /api/posts`
+    action: String,
+    target: String,
+    request_id: Option<String>,
+    trace_id: Option<String>,
+    client_ip: Option<String>,
+    status_code: i32,
+    outcome: String,
+}
+
+pub struct Filter {
+    service_account_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    action: Option<String>,
+    from: Option<OffsetDateTime>,
+    to: Option<OffsetDateTime>,
+    limit: i64,
+}
+
+pub struct AuditLog {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl AuditLog {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+
+    async fn record(&self, entry: &Entry) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO audit_log (id, occurred_at, service_account, token, user_id, action, target, request_id, trace_id, client_ip, status_code, outcome)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+            entry.id,
+            entry.occurred_at,
+            entry.service_account_id,
+            entry.token_id,
+            entry.user_id,
+            entry.action,
+            entry.target,
+            entry.request_id,
+            entry.trace_id,
+            entry.client_ip,
+            entry.status_code,
+            entry.outcome
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(())
+    }
+
+    /// The newest entries matching the filter come first.
+    async fn find(&self, filter: &Filter) -> Result<Vec<Entry>, sqlx::Error> {
+        sqlx::query_as!(
+            Entry,
+            r#"SELECT id, occurred_at, service_account AS service_account_id, token AS token_id, user_id, action, target, request_id, trace_id, client_ip, status_code, outcome
+                FROM audit_log
+                WHERE ($1::uuid IS NULL OR service_account = $1)
+                    AND ($2::uuid IS NULL OR user_id = $2)
+                    AND ($3::text IS NULL OR action = $3)
+                    AND ($4::timestamptz IS NULL OR occurred_at >= $4)
+                    AND ($5::timestamptz IS NULL OR occurred_at < $5)
+                ORDER BY occurred_at DESC
+                LIMIT $6"#,
+            filter.service_account_id,
+            filter.user_id,
+            filter.action,
+            filter.from,
+            filter.to,
+            filter.limit
+        )
+        .fetch_all(self.db_pool.as_ref())
+        .await
+    }
+}
+
+const fn is_mutating(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Records the mutating requests made by service accounts, and by users in the web editor, it has
+/// to run after the authentication. A failure to record is logged, the response is returned either
+/// way, as the action was already done.
+pub async fn middleware<B: Send>(
+    State(audit_log): State<Arc<AuditLog>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let authenticated = request
+        .extensions()
+        .get::<Authenticated>()
+        .map(|x| (Some(x.account().id()), x.token_id(), None))
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<AuthenticatedUser>()
+                .map(|x| (None, None, Some(x.user().id())))
+        });
+
+    let Some((service_account_id, token_id, user_id)) =
+        authenticated.filter(|_| is_mutating(request.method()))
+    else {
+        return next.run(request).await;
+    };
+
+    // Nesting strips the prefix from the URI, the original one has the full path
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path(), |x| x.path())
+        .to_string();
+    let action = format!(
+        "{} {}",
+        request.method(),
+        request
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or(path.as_str(), MatchedPath::as_str)
+    );
+    let request_id = request
+        .headers()
+        .get("X-Request-Id")
+        .and_then(|x| x.to_str().ok())
+        .map(ToString::to_string);
+    let client_ip = request
+        .extensions()
+        .get::<ClientIp>()
+        .and_then(|x| x.0)
+        .map(|x| x.to_string());
+
+    let response = next.run(request).await;
+    if response.extensions().get::<NotAudited>().is_some() {
+        return response;
+    }
+
+    let status = response.status();
+    let entry = Entry {
+        id: Uuid::new_v4(),
+        occurred_at: OffsetDateTime::now_utc(),
+        service_account_id,
+        token_id,
+        user_id,
+        action,
+        target: response
+            .extensions()
+            .get::<AuditTarget>()
+            .map_or(path, |x| x.0.clone()),
+        request_id,
+        trace_id: crate::tracing::current_trace_id(),
+        client_ip,
+        status_code: i32::from(status.as_u16()),
+        outcome: if status.is_success() || status.is_redirection() {
+            "success"
+        } else {
+            "failure"
+        }
+        .to_string(),
+    };
+
+    if let Err(e) = audit_log.record(&entry).await {
+        error!("Failed to record {} in the audit log: {}", entry.action, e);
+    }
+
+    response
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    service_account_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    action: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    to: Option<OffsetDateTime>,
+    limit: Option<i64>,
+}
+
+pub async fn route_api_get_audit(
+    State(audit_log): State<Arc<AuditLog>>,
+    query: Result<Query<AuditQuery>, QueryRejection>,
+) -> Result<Json<Vec<Entry>>, ApiError> {
+    let Query(query) = query?;
+
+    let entries = audit_log
+        .find(&Filter {
+            service_account_id: query.service_account_id,
+            user_id: query.user_id,
+            action: query.action,
+            from: query.from,
+            to: query.to,
+            limit: query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        })
+        .await?;
+
+    Ok(Json(entries))
+}
+
+/// Reading the audit log requires the `accounts:admin` scope.
+pub fn router(audit_log: Arc<AuditLog>) -> Router {
+    Router::new()
+        .route("/audit", get(route_api_get_audit))
+        .route_layer(axum::middleware::from_fn_with_state(
+            Scope::AccountsAdmin,
+            service_accounts::require_scope,
+        ))
+        .with_state(audit_log)
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::Method;
+
+    use super::*;
+
+    #[test]
+    pub fn only_mutating_requests_are_audited() {
+        assert!(is_mutating(&Method::POST));
+        assert!(is_mutating(&Method::PATCH));
+        assert!(is_mutating(&Method::DELETE));
+        assert!(!is_mutating(&Method::GET));
+        assert!(!is_mutating(&Method::HEAD));
+    }
+}
@@ -1,8 +1,27 @@
+use rand::thread_rng;
+
 #[path = "../database.rs"]
 mod database;
 #[path = "../secrets.rs"]
 mod secrets;
 
+// Only the parts of the service accounts that work without the rest of the application
+#[allow(dead_code)]
+#[path = "../service_accounts"]
+mod service_accounts {
+    pub use self::{scope::Scope, signature::Pepper};
+
+    pub mod bootstrap;
+    mod scope;
+    mod signature;
+    mod token;
+}
+
+use service_accounts::{
+    bootstrap::{self, TokenOutput},
+    Pepper,
+};
+
 #[tokio::main]
 async fn main() {
     let db_pool = crate::database::connect(database::AccessLevel::Superuser)
@@ -13,4 +32,20 @@ async fn main() {
         .run(&db_pool)
         .await
         .expect("Failed to run database migrations");
+
+    match std::env::args().nth(1).as_deref() {
+        None => {}
+        // Replaces the root token, e.g. when the one written on the first start got lost
+        Some("reset-root-token") => {
+            let output = TokenOutput::from_env().expect("Invalid ROOT_TOKEN_OUTPUT");
+            let pepper = Pepper::from_secret().expect("Failed to read the request signing pepper");
+            bootstrap::reset_root_token(&db_pool, &pepper, &output, true, thread_rng)
+                .await
+                .expect("Failed to reset the root token");
+        }
+        Some(command) => {
+            eprintln!("Unknown command: {command}, the only one is reset-root-token");
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,365 @@
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{
+        rejection::{FormRejection, JsonRejection, PathRejection},
+        Form, Json, Path, State,
+    },
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use blog_core::api::PreviewRequest;
+use serde::Deserialize;
+use time::{
+    format_description::FormatItem, macros::format_description, OffsetDateTime, PrimitiveDateTime,
+};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    audit::{self, AuditLog, AuditTarget, NotAudited},
+    error::Error,
+    security_headers,
+    users::{self, AuthenticatedUser, SessionStore},
+};
+
+use super::{
+    posts::{read, Post, Repository},
+    views::post::render_view,
+};
+
+/// The format of `<input type="datetime-local">`, the dates are in UTC.
+const DATE_FORMAT: &[FormatItem<'_>] = format_description!("[year]-[month]-[day]T[hour]:[minute]");
+
+struct PostListItem {
+    id: Uuid,
+    title: String,
+    date_published: String,
+    scheduled: bool,
+}
+
+#[derive(Template)]
+#[template(path = "admin/posts.html")]
+struct PostsTemplate {
+    posts: Vec<PostListItem>,
+    csrf_token: String,
+    csp_nonce: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/post_editor.html")]
+struct EditorTemplate {
+    /// Where the form is submitted
+    action: String,
+    title: String,
+    content: String,
+    date_published: String,
+    error: Option<String>,
+    csrf_token: String,
+    csp_nonce: String,
+}
+
+impl EditorTemplate {
+    fn new(user: &AuthenticatedUser, action: String, form: PostForm) -> Self {
+        Self {
+            action,
+            title: form.title,
+            content: form.content,
+            date_published: form.date_published,
+            error: None,
+            csrf_token: user.csrf_token().to_string(),
+            csp_nonce: security_headers::nonce(),
+        }
+    }
+
+    /// Shows the form again with what was submitted, so that nothing is lost.
+    fn with_error(mut self, error: String) -> Result<Response, Error> {
+        self.error = Some(error);
+
+        Ok((StatusCode::UNPROCESSABLE_ENTITY, Html(self.render()?)).into_response())
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct PostForm {
+    title: String,
+    content: String,
+    /// Empty for publishing right away
+    #[serde(default)]
+    date_published: String,
+}
+
+impl PostForm {
+    fn from_post(post: Post) -> Self {
+        Self {
+            title: post.title,
+            content: post.content,
+            date_published: format_date(post.date_published),
+        }
+    }
+
+    fn validate(&self, now: OffsetDateTime) -> Result<(String, OffsetDateTime), String> {
+        let title = self.title.trim();
+        if title.is_empty() {
+            return Err("The title cannot be empty".to_string());
+        }
+
+        let date_published = parse_date(&self.date_published)?.unwrap_or(now);
+
+        Ok((title.to_string(), date_published))
+    }
+}
+
+fn format_date(date: OffsetDateTime) -> String {
+    date.format(DATE_FORMAT).unwrap_or_default()
+}
+
+/// Returns `None` for an empty value. Browsers may add seconds, those are ignored.
+fn parse_date(value: &str) -> Result<Option<OffsetDateTime>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    value
+        .get(..16)
+        .and_then(|x| PrimitiveDateTime::parse(x, DATE_FORMAT).ok())
+        .map(|x| Some(x.assume_utc()))
+        .ok_or_else(|| format!("Invalid publication date: {value}"))
+}
+
+async fn find_post(
+    repository: &Repository,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Post, Error> {
+    let Path(id) = id?;
+
+    repository.find(id).await?.ok_or(Error::NotFound)
+}
+
+pub async fn route_get_posts(
+    State(repository): State<Arc<Repository>>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, Error> {
+    let now = OffsetDateTime::now_utc();
+    let posts = repository.find_all().await?;
+
+    let template = PostsTemplate {
+        posts: posts
+            .into_iter()
+            .map(|x| PostListItem {
+                id: x.id,
+                title: x.title,
+                date_published: format_date(x.date_published),
+                scheduled: x.date_published > now,
+            })
+            .collect(),
+        csrf_token: user.csrf_token().to_string(),
+        csp_nonce: security_headers::nonce(),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn route_get_new_post(user: AuthenticatedUser) -> Result<impl IntoResponse, Error> {
+    let template = EditorTemplate::new(&user, "/admin/posts".to_string(), PostForm::default());
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn route_post_posts(
+    State(repository): State<Arc<Repository>>,
+    user: AuthenticatedUser,
+    form: Result<Form<PostForm>, FormRejection>,
+) -> Result<Response, Error> {
+    let Form(form) = form?;
+
+    let (title, date_published) = match form.validate(OffsetDateTime::now_utc()) {
+        Ok(x) => x,
+        Err(e) => {
+            return EditorTemplate::new(&user, "/admin/posts".to_string(), form).with_error(e)
+        }
+    };
+
+    let id = Uuid::new_v4();
+    repository
+        .create(&Post {
+            id,
+            date_published,
+            title,
+            content: form.content,
+            slug: None,
+            description: None,
+            tags: vec![],
+        })
+        .await?;
+
+    info!(user.name = user.user().name(), "Post {} created", id);
+
+    let path = format!("/admin/posts/{id}");
+
+    Ok((Extension(AuditTarget(path.clone())), Redirect::to(&path)).into_response())
+}
+
+pub async fn route_get_post(
+    State(repository): State<Arc<Repository>>,
+    user: AuthenticatedUser,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<impl IntoResponse, Error> {
+    let post = find_post(&repository, id).await?;
+    let template = EditorTemplate::new(
+        &user,
+        format!("/admin/posts/{}", post.id),
+        PostForm::from_post(post),
+    );
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn route_post_post(
+    State(repository): State<Arc<Repository>>,
+    user: AuthenticatedUser,
+    id: Result<Path<Uuid>, PathRejection>,
+    form: Result<Form<PostForm>, FormRejection>,
+) -> Result<Response, Error> {
+    let Form(form) = form?;
+    let mut post = find_post(&repository, id).await?;
+    let action = format!("/admin/posts/{}", post.id);
+
+    let (title, date_published) = match form.validate(OffsetDateTime::now_utc()) {
+        Ok(x) => x,
+        Err(e) => return EditorTemplate::new(&user, action, form).with_error(e),
+    };
+
+    // The rest, like the slug and the tags, is only changed through the API
+    post.title = title;
+    post.content = form.content;
+    post.date_published = date_published;
+
+    if !repository.update(&post).await? {
+        return Err(Error::NotFound);
+    }
+
+    info!(user.name = user.user().name(), "Post {} updated", post.id);
+
+    Ok(Redirect::to(&action).into_response())
+}
+
+pub async fn route_post_post_delete(
+    State(repository): State<Arc<Repository>>,
+    user: AuthenticatedUser,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<impl IntoResponse, Error> {
+    let Path(id) = id?;
+
+    if !repository.delete(id).await? {
+        return Err(Error::NotFound);
+    }
+
+    info!(user.name = user.user().name(), "Post {} deleted", id);
+
+    Ok(Redirect::to("/admin/posts"))
+}
+
+/// Renders the post exactly like the public page does, the editor shows it in an iframe. It's also
+/// in the API, for `admin preview`. It's requested whenever the text changes, so it's not audited.
+pub async fn route_post_preview(
+    request: Result<Json<PreviewRequest>, JsonRejection>,
+) -> impl IntoResponse {
+    (Extension(NotAudited), render_preview(request))
+}
+
+fn render_preview(
+    request: Result<Json<PreviewRequest>, JsonRejection>,
+) -> Result<Html<String>, Error> {
+    let Json(request) = request?;
+
+    let template = render_view(
+        read::Post {
+            id: Uuid::nil(),
+            title: request.title,
+            content: request.content,
+            description: request.description,
+        },
+        security_headers::nonce(),
+    );
+
+    Ok(Html(template.render()?))
+}
+
+/// The latest draft pushed by `admin watch`, rendered like the public page.
+pub async fn route_get_draft(
+    State(repository): State<Arc<Repository>>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<impl IntoResponse, Error> {
+    let Path(id) = id?;
+    let draft = repository.find_draft(id).await?.ok_or(Error::NotFound)?;
+
+    let template = render_view(
+        read::Post {
+            id: draft.id,
+            title: draft.title,
+            content: draft.content,
+            description: draft.description,
+        },
+        security_headers::nonce(),
+    );
+
+    Ok(Html(template.render()?))
+}
+
+/// The post editor, all of it requires a session. The changes are recorded in the audit log.
+pub fn router(
+    repository: Arc<Repository>,
+    sessions: Arc<SessionStore>,
+    audit_log: Arc<AuditLog>,
+) -> Router {
+    Router::new()
+        .route("/admin/posts", get(route_get_posts).post(route_post_posts))
+        .route("/admin/posts/new", get(route_get_new_post))
+        .route(
+            "/admin/posts/:id",
+            get(route_get_post).post(route_post_post),
+        )
+        .route("/admin/posts/:id/delete", post(route_post_post_delete))
+        .route("/admin/preview", post(route_post_preview))
+        .route("/admin/drafts/:id", get(route_get_draft))
+        .route_layer(axum::middleware::from_fn_with_state(
+            audit_log,
+            audit::middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            sessions,
+            users::session::middleware,
+        ))
+        .with_state(repository)
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    pub fn can_parse_dates() {
+        assert_eq!(
+            Ok(Some(datetime!(2026-10-19 12:30 UTC))),
+            parse_date("2026-10-19T12:30")
+        );
+        assert_eq!(
+            Ok(Some(datetime!(2026-10-19 12:30 UTC))),
+            parse_date("2026-10-19T12:30:15")
+        );
+        assert_eq!(Ok(None), parse_date(" "));
+        assert!(parse_date("tomorrow").is_err());
+
+        assert_eq!(
+            "2026-10-19T12:30",
+            format_date(datetime!(2026-10-19 12:30:15 UTC))
+        );
+    }
+}
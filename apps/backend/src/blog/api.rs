@@ -0,0 +1,444 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use time::OffsetDateTime;
+use tracing::info;
+use uuid::Uuid;
+
+use blog_core::api::{
+    content_sha256, DraftPutRequest, DraftResponse, PostCreateRequest, PostPutRequest,
+    PostResponse, PostSummaryResponse, PostUpdateRequest, PostsQuery,
+};
+
+use crate::{
+    audit::AuditTarget,
+    error::{ApiError, Error},
+    service_accounts::{self, Authenticated, Scope},
+};
+
+use super::posts::{DeletedPost, Draft, Post, Repository};
+
+fn post_response(post: Post, now: OffsetDateTime) -> PostResponse {
+    PostResponse {
+        id: post.id,
+        title: post.title,
+        date_published: post.date_published,
+        published: post.date_published <= now,
+        content: post.content,
+        slug: post.slug,
+        description: post.description,
+        tags: post.tags,
+    }
+}
+
+fn post_summary_response(post: Post, now: OffsetDateTime) -> PostSummaryResponse {
+    PostSummaryResponse {
+        id: post.id,
+        title: post.title,
+        date_published: post.date_published,
+        published: post.date_published <= now,
+        slug: post.slug,
+        description: post.description,
+        tags: post.tags,
+        content_sha256: content_sha256(&post.content),
+        deleted_at: None,
+    }
+}
+
+fn deleted_post_summary_response(post: DeletedPost, now: OffsetDateTime) -> PostSummaryResponse {
+    let deleted_at = post.deleted_at;
+
+    PostSummaryResponse {
+        deleted_at: Some(deleted_at),
+        ..post_summary_response(
+            Post {
+                id: post.id,
+                date_published: post.date_published,
+                title: post.title,
+                content: post.content,
+                slug: post.slug,
+                description: post.description,
+                tags: post.tags,
+            },
+            now,
+        )
+    }
+}
+
+fn validate_title(title: &str) -> Result<(), Error> {
+    if title.trim().is_empty() {
+        return Err(Error::Validation("The title cannot be empty".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Slugs are used in URLs as they are, so they have to be lowercase words separated by dashes.
+fn validate_slug(slug: Option<&str>) -> Result<(), Error> {
+    match slug {
+        Some("") => Err(Error::Validation("The slug cannot be empty".to_string())),
+        Some(slug) if slug::slugify(slug) != slug => Err(Error::Validation(format!(
+            "Invalid slug {slug}, it could be {}",
+            slug::slugify(slug)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn validate_tags(tags: &[String]) -> Result<(), Error> {
+    if tags.iter().any(|x| x.trim().is_empty()) {
+        return Err(Error::Validation("The tags cannot be empty".to_string()));
+    }
+
+    Ok(())
+}
+
+async fn find_post(
+    repository: &Repository,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Post, Error> {
+    let Path(id) = id?;
+
+    repository.find(id).await?.ok_or(Error::NotFound)
+}
+
+pub async fn route_api_get_posts(
+    State(repository): State<Arc<Repository>>,
+    Query(query): Query<PostsQuery>,
+) -> Result<Json<Vec<PostSummaryResponse>>, ApiError> {
+    let now = OffsetDateTime::now_utc();
+
+    let posts = if query.deleted {
+        repository
+            .find_deleted()
+            .await?
+            .into_iter()
+            .map(|x| deleted_post_summary_response(x, now))
+            .collect()
+    } else {
+        repository
+            .find_all()
+            .await?
+            .into_iter()
+            .map(|x| post_summary_response(x, now))
+            .collect()
+    };
+
+    Ok(Json(posts))
+}
+
+pub async fn route_api_get_post(
+    State(repository): State<Arc<Repository>>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<PostResponse>, ApiError> {
+    let post = find_post(&repository, id).await?;
+
+    Ok(Json(post_response(post, OffsetDateTime::now_utc())))
+}
+
+pub async fn route_api_post_posts(
+    State(repository): State<Arc<Repository>>,
+    authenticated: Authenticated,
+    request: Result<Json<PostCreateRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(request) = request?;
+    validate_title(&request.title)?;
+    validate_slug(request.slug.as_deref())?;
+    validate_tags(&request.tags)?;
+
+    let now = OffsetDateTime::now_utc();
+    let post = Post {
+        id: request.id,
+        date_published: now,
+        title: request.title,
+        content: request.content,
+        slug: request.slug,
+        description: request.description,
+        tags: request.tags,
+    };
+    repository.create(&post).await?;
+
+    info!(
+        token.id = ?authenticated.token_id(),
+        "Post {} created by service account {}",
+        request.id,
+        authenticated.account().id()
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Extension(AuditTarget(format!("/api/posts/{}", request.id))),
+        Json(post_response(post, now)),
+    ))
+}
+
+/// Creates the post with the ID chosen by the client, or replaces it, so that publishing the
+/// same file again updates the post instead of creating another one.
+pub async fn route_api_put_post(
+    State(repository): State<Arc<Repository>>,
+    authenticated: Authenticated,
+    id: Result<Path<Uuid>, PathRejection>,
+    request: Result<Json<PostPutRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(id) = id?;
+    let Json(request) = request?;
+    validate_title(&request.title)?;
+    validate_slug(request.slug.as_deref())?;
+    validate_tags(&request.tags)?;
+
+    let now = OffsetDateTime::now_utc();
+    let date_published = match request.date_published {
+        Some(date_published) => date_published,
+        None => repository.find(id).await?.map_or(now, |x| x.date_published),
+    };
+
+    let post = Post {
+        id,
+        date_published,
+        title: request.title,
+        content: request.content,
+        slug: request.slug,
+        description: request.description,
+        tags: request.tags,
+    };
+
+    let Some(created) = repository.upsert(&post).await? else {
+        return Err(Error::Conflict(format!("The post {id} is deleted, restore it first")).into());
+    };
+    repository.delete_draft(id).await?;
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        post.id = %id,
+        "Post {}",
+        if created { "created" } else { "replaced" }
+    );
+
+    Ok((
+        if created {
+            StatusCode::CREATED
+        } else {
+            StatusCode::OK
+        },
+        Json(post_response(post, now)),
+    ))
+}
+
+/// Saves the draft of the post, validated like the post itself, so that the mistakes show up
+/// while writing. The post doesn't have to exist yet.
+pub async fn route_api_put_draft(
+    State(repository): State<Arc<Repository>>,
+    authenticated: Authenticated,
+    id: Result<Path<Uuid>, PathRejection>,
+    request: Result<Json<DraftPutRequest>, JsonRejection>,
+) -> Result<Json<DraftResponse>, ApiError> {
+    let Path(id) = id?;
+    let Json(request) = request?;
+    validate_title(&request.title)?;
+    validate_slug(request.slug.as_deref())?;
+    validate_tags(&request.tags)?;
+
+    let updated_at = OffsetDateTime::now_utc();
+    repository
+        .save_draft(&Draft {
+            id,
+            title: request.title,
+            content: request.content,
+            slug: request.slug,
+            description: request.description,
+            tags: request.tags,
+            updated_at,
+        })
+        .await?;
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        post.id = %id,
+        "Draft saved"
+    );
+
+    Ok(Json(DraftResponse { id, updated_at }))
+}
+
+pub async fn route_api_patch_post(
+    State(repository): State<Arc<Repository>>,
+    authenticated: Authenticated,
+    id: Result<Path<Uuid>, PathRejection>,
+    request: Result<Json<PostUpdateRequest>, JsonRejection>,
+) -> Result<Json<PostResponse>, ApiError> {
+    let Json(request) = request?;
+    let mut post = find_post(&repository, id).await?;
+
+    if let Some(title) = request.title {
+        validate_title(&title)?;
+        post.title = title;
+    }
+    if let Some(content) = request.content {
+        post.content = content;
+    }
+    if let Some(date_published) = request.date_published {
+        post.date_published = date_published;
+    }
+
+    if !repository.update(&post).await? {
+        return Err(Error::NotFound.into());
+    }
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        post.id = %post.id,
+        "Post updated"
+    );
+
+    Ok(Json(post_response(post, OffsetDateTime::now_utc())))
+}
+
+pub async fn route_api_delete_post(
+    State(repository): State<Arc<Repository>>,
+    authenticated: Authenticated,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Path(id) = id?;
+
+    if !repository.delete(id).await? {
+        return Err(Error::NotFound.into());
+    }
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        post.id = %id,
+        "Post deleted"
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn route_api_post_restore(
+    State(repository): State<Arc<Repository>>,
+    authenticated: Authenticated,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<PostResponse>, ApiError> {
+    let Path(id) = id?;
+
+    if !repository.restore(id).await? {
+        return Err(Error::NotFound.into());
+    }
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        post.id = %id,
+        "Post restored"
+    );
+
+    let post = repository.find(id).await?.ok_or(Error::NotFound)?;
+
+    Ok(Json(post_response(post, OffsetDateTime::now_utc())))
+}
+
+/// The posts endpoints, reading needs `posts:read`, changes `posts:write`, and deleting or
+/// restoring `posts:delete`.
+pub fn router(repository: Arc<Repository>) -> Router {
+    let scoped = |router: Router<Arc<Repository>>, scope| {
+        router.route_layer(axum::middleware::from_fn_with_state(
+            scope,
+            service_accounts::require_scope,
+        ))
+    };
+
+    let read = Router::new()
+        .route("/posts", get(route_api_get_posts))
+        .route("/posts/:id", get(route_api_get_post));
+    let write = Router::new()
+        .route("/posts", post(route_api_post_posts))
+        .route(
+            "/posts/:id",
+            axum::routing::put(route_api_put_post).patch(route_api_patch_post),
+        )
+        .route("/drafts/:id", axum::routing::put(route_api_put_draft))
+        .route("/preview", post(super::admin::route_post_preview));
+    let delete = Router::new()
+        .route("/posts/:id", axum::routing::delete(route_api_delete_post))
+        .route("/posts/:id/restore", post(route_api_post_restore));
+
+    scoped(read, Scope::PostsRead)
+        .merge(scoped(write, Scope::PostsWrite))
+        .merge(scoped(delete, Scope::PostsDelete))
+        .with_state(repository)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+    use time::macros::datetime;
+
+    use super::*;
+
+    /// The fixtures of the round-trip tests of the API types, which the client parses.
+    fn fixture(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn post(slug: Option<&str>, description: Option<&str>, tags: &[&str]) -> Post {
+        Post {
+            id: "5f0c1c2e-1111-4222-8333-944455556666".parse().unwrap(),
+            date_published: datetime!(2026-10-19 10:00 UTC),
+            title: "Hello".to_string(),
+            content: "# Hello".to_string(),
+            slug: slug.map(ToString::to_string),
+            description: description.map(ToString::to_string),
+            tags: tags.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    pub fn responds_with_the_client_json() {
+        let now = datetime!(2026-10-20 12:00 UTC);
+
+        assert_eq!(
+            fixture(include_str!("../../../blog-core/fixtures/post.json")),
+            serde_json::to_value(post_response(post(Some("hello"), None, &["meta"]), now)).unwrap()
+        );
+        assert_eq!(
+            fixture(include_str!(
+                "../../../blog-core/fixtures/post_summary.json"
+            )),
+            serde_json::to_value(post_summary_response(post(None, Some("About"), &[]), now))
+                .unwrap()
+        );
+
+        let post = post(None, Some("About"), &[]);
+        let deleted = DeletedPost {
+            id: post.id,
+            date_published: post.date_published,
+            title: post.title,
+            content: post.content,
+            slug: post.slug,
+            description: post.description,
+            tags: post.tags,
+            deleted_at: datetime!(2026-10-20 08:30 UTC),
+        };
+        assert_eq!(
+            fixture(include_str!(
+                "../../../blog-core/fixtures/deleted_post_summary.json"
+            )),
+            serde_json::to_value(deleted_post_summary_response(deleted, now)).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn validates_slugs() {
+        assert!(validate_slug(None).is_ok());
+        assert!(validate_slug(Some("hello-world-2")).is_ok());
+        assert!(validate_slug(Some("Hello World")).is_err());
+        assert!(validate_slug(Some("")).is_err());
+    }
+}
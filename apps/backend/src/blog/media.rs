@@ -0,0 +1,245 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{rejection::PathRejection, DefaultBodyLimit, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, put},
+    Json, Router,
+};
+use sqlx::{Pool, Postgres};
+use tracing::info;
+
+use blog_core::api::{media_path, media_sha256, MediaResponse, MEDIA_TYPES};
+
+use crate::{
+    error::{ApiError, Error},
+    service_accounts::{self, Authenticated, Scope},
+};
+
+/// The largest file that can be uploaded.
+pub const MAX_SIZE: usize = 8 * 1024 * 1024;
+
+/// The content of a hash never changes, so it can be cached forever.
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// An image or another file the posts link to, see [`blog_core::api::media_sha256`].
+pub struct Media {
+    pub sha256: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// A media file without its content.
+pub struct MediaInfo {
+    pub sha256: String,
+    pub content_type: String,
+    pub size: i64,
+}
+
+pub struct Repository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl Repository {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn find(&self, sha256: &str) -> Result<Option<Media>, sqlx::Error> {
+        sqlx::query_as!(
+            Media,
+            "SELECT sha256, content_type, content FROM media WHERE sha256 = $1",
+            sha256
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await
+    }
+
+    pub async fn find_info(&self, sha256: &str) -> Result<Option<MediaInfo>, sqlx::Error> {
+        sqlx::query_as!(
+            MediaInfo,
+            r#"SELECT sha256, content_type, octet_length(content)::BIGINT AS "size!" FROM media
+                WHERE sha256 = $1"#,
+            sha256
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await
+    }
+
+    /// Returns `false` if the file was already stored, it's the same content.
+    pub async fn create(&self, media: &Media) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO media (sha256, content_type, content) VALUES($1, $2, $3)
+                ON CONFLICT (sha256) DO NOTHING",
+            media.sha256,
+            media.content_type,
+            media.content
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+fn is_sha256(sha256: &str) -> bool {
+    sha256.len() == 64
+        && sha256
+            .bytes()
+            .all(|x| x.is_ascii_digit() || (b'a'..=b'f').contains(&x))
+}
+
+fn media_response(info: MediaInfo) -> MediaResponse {
+    MediaResponse {
+        path: media_path(&info.sha256),
+        sha256: info.sha256,
+        content_type: info.content_type,
+        size: info.size.try_into().unwrap_or_default(),
+    }
+}
+
+/// Serves the file to everyone, the posts link to it.
+pub async fn route_get_media(
+    State(repository): State<Arc<Repository>>,
+    sha256: Result<Path<String>, PathRejection>,
+) -> Result<impl IntoResponse, Error> {
+    let Path(sha256) = sha256?;
+    if !is_sha256(&sha256) {
+        return Err(Error::NotFound);
+    }
+
+    let media = repository.find(&sha256).await?.ok_or(Error::NotFound)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, media.content_type),
+            (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+        ],
+        media.content,
+    ))
+}
+
+/// Tells whether the file is already stored, so that it isn't uploaded again, without reading it.
+pub async fn route_head_media(
+    State(repository): State<Arc<Repository>>,
+    sha256: Result<Path<String>, PathRejection>,
+) -> Result<impl IntoResponse, Error> {
+    let Path(sha256) = sha256?;
+    if !is_sha256(&sha256) {
+        return Err(Error::NotFound);
+    }
+
+    let info = repository
+        .find_info(&sha256)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok([
+        (header::CONTENT_TYPE, info.content_type),
+        (header::CONTENT_LENGTH, info.size.to_string()),
+        (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+    ])
+}
+
+/// Stores the body under the hash of its content, which has to be the one in the path, with the
+/// content type it's served with.
+pub async fn route_api_put_media(
+    State(repository): State<Arc<Repository>>,
+    authenticated: Authenticated,
+    sha256: Result<Path<String>, PathRejection>,
+    headers: HeaderMap,
+    content: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let Path(sha256) = sha256?;
+    if media_sha256(&content) != sha256 {
+        return Err(
+            Error::Validation("The path is not the SHA-256 of the content".to_string()).into(),
+        );
+    }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default();
+    if !MEDIA_TYPES.iter().any(|(_, x)| *x == content_type) {
+        let mut allowed: Vec<&str> = MEDIA_TYPES.iter().map(|(_, x)| *x).collect();
+        allowed.dedup();
+
+        return Err(Error::Validation(format!(
+            "The content type {content_type:?} is not allowed, it has to be one of {}",
+            allowed.join(", ")
+        ))
+        .into());
+    }
+
+    let media = Media {
+        sha256,
+        content_type: content_type.to_string(),
+        content: content.to_vec(),
+    };
+    let created = repository.create(&media).await?;
+
+    if created {
+        info!(
+            service_account.id = %authenticated.account().id(),
+            media.sha256 = %media.sha256,
+            media.size = media.content.len(),
+            "Media uploaded"
+        );
+    }
+
+    // The stored file may have been uploaded with another content type
+    let info = repository
+        .find_info(&media.sha256)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok((
+        if created {
+            StatusCode::CREATED
+        } else {
+            StatusCode::OK
+        },
+        Json(media_response(info)),
+    ))
+}
+
+/// The public route serving the files.
+pub fn router(repository: Arc<Repository>) -> Router {
+    Router::new()
+        .route(
+            "/media/:sha256",
+            get(route_get_media).head(route_head_media),
+        )
+        .with_state(repository)
+}
+
+/// Uploading a file needs [`Scope::PostsWrite`], whether it's there already is told by the public
+/// route.
+pub fn api_router(repository: Arc<Repository>) -> Router {
+    Router::new()
+        .route(
+            "/media/:sha256",
+            put(route_api_put_media).layer(DefaultBodyLimit::max(MAX_SIZE)),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            Scope::PostsWrite,
+            service_accounts::require_scope,
+        ))
+        .with_state(repository)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn validates_hashes() {
+        assert!(is_sha256(&media_sha256(b"")));
+        assert!(!is_sha256(&media_sha256(b"").to_uppercase()));
+        assert!(!is_sha256("../posts"));
+        assert!(!is_sha256(""));
+    }
+}
@@ -2,20 +2,21 @@ use std::sync::Arc;
 
 use askama::Template;
 use axum::{
-    extract::{Json, Path, Query, State},
+    extract::{rejection::PathRejection, Path, Query, State},
     response::{Html, IntoResponse},
 };
 
 use serde::Deserialize;
 use sqlx::{Pool, Postgres};
-use time::OffsetDateTime;
 use uuid::Uuid;
 
-use self::{
-    posts::{read, Post, Repository},
-    views::post::render_view,
-};
+use crate::{error::Error, security_headers};
+
+use self::{posts::read, views::post::render_view};
 
+pub mod admin;
+pub mod api;
+pub mod media;
 pub mod posts;
 mod views;
 
@@ -28,6 +29,7 @@ struct LatestPostView {
 #[template(path = "index.html")]
 struct IndexTemplate {
     posts: Vec<LatestPostView>,
+    csp_nonce: String,
 }
 
 #[derive(Deserialize)]
@@ -39,39 +41,15 @@ pub struct Blog {
     posts: read::Read,
 }
 
-#[derive(Deserialize)]
-pub struct PostCreateRequest {
-    id: Uuid,
-    title: String,
-    content: String,
-}
-
-pub async fn route_api_post_posts(
-    State(repository): State<Arc<Repository>>,
-    Json(request): Json<PostCreateRequest>,
-) -> impl IntoResponse {
-    repository
-        .create(Post {
-            id: request.id,
-            date_published: OffsetDateTime::now_utc(),
-            title: request.title,
-            content: request.content,
-        })
-        .await
-        .unwrap();
-
-    (axum::http::StatusCode::CREATED, "")
-}
-
 pub async fn route_main(
     Query(query): Query<QueryString>,
     State(blog): State<Arc<Blog>>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, Error> {
     if query.preview.unwrap_or(0) != 1 {
-        return (axum::http::StatusCode::NOT_FOUND, Html(String::new()));
+        return Err(Error::NotFound);
     }
 
-    let latest = blog.posts.latest(10).await.unwrap();
+    let latest = blog.posts.latest(10).await?;
 
     let template = IndexTemplate {
         posts: latest
@@ -81,23 +59,29 @@ pub async fn route_main(
                 title: x.title,
             })
             .collect(),
+        csp_nonce: security_headers::nonce(),
     };
-    (axum::http::StatusCode::OK, Html(template.render().unwrap()))
+    Ok(Html(template.render()?))
 }
 
 pub async fn route_posts_id(
-    Path(id): Path<Uuid>,
+    id: Result<Path<Uuid>, PathRejection>,
     State(blog): State<Arc<Blog>>,
-) -> impl IntoResponse {
-    let post = blog.posts.single(id).await.unwrap().unwrap();
+) -> Result<impl IntoResponse, Error> {
+    let Path(id) = id?;
+    let post = blog.posts.single(id).await?.ok_or(Error::NotFound)?;
 
-    let template = render_view(post);
+    let template = render_view(post, security_headers::nonce());
+
+    Ok(Html(template.render()?))
+}
 
-    (axum::http::StatusCode::OK, Html(template.render().unwrap()))
+pub async fn route_not_found() -> Error {
+    Error::NotFound
 }
 
 impl Blog {
-    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
         Self {
             posts: read::Read::new(db_pool),
         }
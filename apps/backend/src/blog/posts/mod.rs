@@ -12,6 +12,33 @@ pub struct Post {
     pub date_published: OffsetDateTime,
     pub title: String,
     pub content: String,
+    /// Unique among all the posts, including the deleted ones
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// A post in the trash, it can still be restored.
+pub struct DeletedPost {
+    pub id: Uuid,
+    pub date_published: OffsetDateTime,
+    pub title: String,
+    pub content: String,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub deleted_at: OffsetDateTime,
+}
+
+/// What's being written, it doesn't change the published post until it's published.
+pub struct Draft {
+    pub id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub updated_at: OffsetDateTime,
 }
 
 pub struct Repository {
@@ -22,28 +49,200 @@ pub struct Repository {
 pub enum Error {
     #[error("Database Error: {0}")]
     Sqlx(#[from] sqlx::Error),
+    #[error("Another post already has the slug {0}")]
+    DuplicateSlug(String),
+}
+
+impl Error {
+    fn from_sqlx(error: sqlx::Error, slug: Option<&str>) -> Self {
+        match &error {
+            sqlx::Error::Database(e) if e.constraint() == Some("posts_slug_key") => {
+                Self::DuplicateSlug(slug.unwrap_or_default().to_string())
+            }
+            _ => Self::Sqlx(error),
+        }
+    }
 }
 
 impl Repository {
-    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
         Self { db_pool }
     }
 
-    pub async fn create(&self, post: Post) -> Result<(), Error> {
-        let mut transaction = self.db_pool.begin().await?;
-
+    pub async fn create(&self, post: &Post) -> Result<(), Error> {
         sqlx::query!(
-            "INSERT INTO posts (id, date_published, title, content) VALUES($1, $2, $3, $4)",
+            "INSERT INTO posts (id, date_published, title, content, slug, description, tags)
+                VALUES($1, $2, $3, $4, $5, $6, $7)",
+            post.id,
+            post.date_published,
+            post.title,
+            post.content,
+            post.slug,
+            post.description,
+            &post.tags
+        )
+        .execute(self.db_pool.as_ref())
+        .await
+        .map_err(|e| Error::from_sqlx(e, post.slug.as_deref()))?;
+
+        Ok(())
+    }
+
+    /// Creates the post, or replaces the one with the same ID, so that publishing the same post
+    /// again changes nothing. Returns `Some(true)` if the post was created, and `None` if it was
+    /// deleted, it has to be restored first.
+    pub async fn upsert(&self, post: &Post) -> Result<Option<bool>, Error> {
+        let created = sqlx::query_scalar!(
+            r#"INSERT INTO posts (id, date_published, title, content, slug, description, tags)
+                VALUES($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (id) DO UPDATE SET
+                    date_published = EXCLUDED.date_published,
+                    title = EXCLUDED.title,
+                    content = EXCLUDED.content,
+                    slug = EXCLUDED.slug,
+                    description = EXCLUDED.description,
+                    tags = EXCLUDED.tags
+                WHERE posts.deleted_at IS NULL
+                RETURNING xmax = 0 AS "created!""#,
+            post.id,
+            post.date_published,
+            post.title,
+            post.content,
+            post.slug,
+            post.description,
+            &post.tags
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await
+        .map_err(|e| Error::from_sqlx(e, post.slug.as_deref()))?;
+
+        Ok(created)
+    }
+
+    /// All the posts, including the scheduled ones, the newest first.
+    pub async fn find_all(&self) -> Result<Vec<Post>, Error> {
+        Ok(sqlx::query_as!(
+            Post,
+            "SELECT id, date_published, title, content, slug, description, tags FROM posts
+                WHERE deleted_at IS NULL ORDER BY date_published DESC"
+        )
+        .fetch_all(self.db_pool.as_ref())
+        .await?)
+    }
+
+    pub async fn find(&self, id: Uuid) -> Result<Option<Post>, Error> {
+        Ok(sqlx::query_as!(
+            Post,
+            "SELECT id, date_published, title, content, slug, description, tags FROM posts
+                WHERE id = $1 AND deleted_at IS NULL",
+            id
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await?)
+    }
+
+    /// The deleted posts, the most recently deleted first.
+    pub async fn find_deleted(&self) -> Result<Vec<DeletedPost>, Error> {
+        Ok(sqlx::query_as!(
+            DeletedPost,
+            r#"SELECT id, date_published, title, content, slug, description, tags,
+                    deleted_at AS "deleted_at!"
+                FROM posts WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"#
+        )
+        .fetch_all(self.db_pool.as_ref())
+        .await?)
+    }
+
+    /// Returns `false` if there's no such post.
+    pub async fn update(&self, post: &Post) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            "UPDATE posts
+                SET date_published = $2, title = $3, content = $4, slug = $5, description = $6,
+                    tags = $7
+                WHERE id = $1 AND deleted_at IS NULL",
             post.id,
             post.date_published,
             post.title,
-            post.content
+            post.content,
+            post.slug,
+            post.description,
+            &post.tags
+        )
+        .execute(self.db_pool.as_ref())
+        .await
+        .map_err(|e| Error::from_sqlx(e, post.slug.as_deref()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Only marks the post as deleted, so that it can be restored. Returns `false` if there's no
+    /// such post, or it's deleted already.
+    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            "UPDATE posts SET deleted_at = now() WHERE id = $1 AND deleted_at IS NULL",
+            id
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replaces the previous draft of the post.
+    pub async fn save_draft(&self, draft: &Draft) -> Result<(), Error> {
+        sqlx::query!(
+            "INSERT INTO post_drafts (id, title, content, slug, description, tags, updated_at)
+                VALUES($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (id) DO UPDATE SET
+                    title = EXCLUDED.title,
+                    content = EXCLUDED.content,
+                    slug = EXCLUDED.slug,
+                    description = EXCLUDED.description,
+                    tags = EXCLUDED.tags,
+                    updated_at = EXCLUDED.updated_at",
+            draft.id,
+            draft.title,
+            draft.content,
+            draft.slug,
+            draft.description,
+            &draft.tags,
+            draft.updated_at
         )
-        .execute(&mut *transaction)
+        .execute(self.db_pool.as_ref())
         .await?;
 
-        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn find_draft(&self, id: Uuid) -> Result<Option<Draft>, Error> {
+        Ok(sqlx::query_as!(
+            Draft,
+            "SELECT id, title, content, slug, description, tags, updated_at FROM post_drafts
+                WHERE id = $1",
+            id
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await?)
+    }
+
+    /// Once the post is published, its draft is out of date.
+    pub async fn delete_draft(&self, id: Uuid) -> Result<(), Error> {
+        sqlx::query!("DELETE FROM post_drafts WHERE id = $1", id)
+            .execute(self.db_pool.as_ref())
+            .await?;
 
         Ok(())
     }
+
+    /// Returns `false` if there's no such deleted post.
+    pub async fn restore(&self, id: Uuid) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            "UPDATE posts SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL",
+            id
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
 }
@@ -3,6 +3,8 @@ use std::sync::Arc;
 use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
+/// Only the posts that are published already, the ones scheduled for later or deleted are not
+/// visible.
 pub struct Read {
     db_pool: Arc<Pool<Postgres>>,
 }
@@ -13,20 +15,23 @@ pub struct LatestPost {
 }
 
 pub struct Post {
+    #[allow(unused)]
     pub id: Uuid,
     pub title: String,
     pub content: String,
+    pub description: Option<String>,
 }
 
 impl Read {
-    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
         Self { db_pool }
     }
 
     pub async fn single(&self, id: Uuid) -> Result<Option<Post>, sqlx::Error> {
         sqlx::query_as!(
             Post,
-            "SELECT id, title, content FROM posts WHERE id = $1",
+            "SELECT id, title, content, description FROM posts
+                WHERE id = $1 AND date_published <= now() AND deleted_at IS NULL",
             id
         )
         .fetch_optional(self.db_pool.as_ref())
@@ -36,7 +41,8 @@ impl Read {
     pub async fn latest(&self, count: i64) -> Result<Vec<LatestPost>, sqlx::Error> {
         sqlx::query_as!(
             LatestPost,
-            "SELECT id, title FROM posts ORDER BY date_published DESC LIMIT $1",
+            "SELECT id, title FROM posts WHERE date_published <= now() AND deleted_at IS NULL
+                ORDER BY date_published DESC LIMIT $1",
             count
         )
         .fetch_all(self.db_pool.as_ref())
@@ -0,0 +1,209 @@
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    str::FromStr,
+    sync::Arc,
+};
+
+use axum::{
+    extract::{connect_info::ConnectInfo, State},
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use thiserror::Error;
+
+use crate::server::PeerAddress;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid network: {0}")]
+    InvalidNetwork(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Network {
+    address: Ipv6Addr,
+    prefix_length: u8,
+}
+
+impl Network {
+    fn contains(&self, address: IpAddr) -> bool {
+        let mask = u128::MAX
+            .checked_shl(128 - u32::from(self.prefix_length))
+            .unwrap_or(0);
+
+        u128::from(to_ipv6(address)) & mask == u128::from(self.address) & mask
+    }
+}
+
+impl FromStr for Network {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (address, prefix_length) = s.split_once('/').unwrap_or((s, ""));
+        let address: IpAddr = address
+            .parse()
+            .map_err(|_| Error::InvalidNetwork(s.to_string()))?;
+
+        let max_prefix_length = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_length = if prefix_length.is_empty() {
+            max_prefix_length
+        } else {
+            prefix_length
+                .parse()
+                .map_err(|_| Error::InvalidNetwork(s.to_string()))?
+        };
+
+        if prefix_length > max_prefix_length {
+            return Err(Error::InvalidNetwork(s.to_string()));
+        }
+
+        Ok(Self {
+            address: to_ipv6(address),
+            // IPv4 addresses are compared as IPv4-mapped IPv6 addresses
+            prefix_length: prefix_length + (128 - max_prefix_length),
+        })
+    }
+}
+
+const fn to_ipv6(address: IpAddr) -> Ipv6Addr {
+    match address {
+        IpAddr::V4(address) => address.to_ipv6_mapped(),
+        IpAddr::V6(address) => address,
+    }
+}
+
+/// The proxies (e.g. the ingress controller) whose `X-Forwarded-For` headers can be believed.
+#[derive(Debug, Default)]
+pub struct TrustedProxies {
+    networks: Vec<Network>,
+}
+
+impl TrustedProxies {
+    /// Reads the comma-separated list of addresses or CIDR networks from `TRUSTED_PROXIES`.
+    pub fn from_env() -> Result<Self, Error> {
+        std::env::var("TRUSTED_PROXIES").map_or_else(|_| Ok(Self::default()), |x| x.parse())
+    }
+
+    fn contains(&self, address: IpAddr) -> bool {
+        self.networks.iter().any(|x| x.contains(address))
+    }
+
+    /// Finds the address of the client, walking `X-Forwarded-For` from the right and stopping at
+    /// the first address that is not a trusted proxy. Connections over a Unix socket come from a
+    /// local proxy, so they're always trusted.
+    fn resolve(&self, peer: Option<IpAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        if let Some(peer) = peer {
+            if !self.contains(peer) {
+                return Some(peer);
+            }
+        }
+
+        let mut client = peer;
+        for address in forwarded_for.unwrap_or("").rsplit(',') {
+            let Ok(address) = address.trim().parse::<IpAddr>() else {
+                break;
+            };
+
+            client = Some(address);
+
+            if !self.contains(address) {
+                break;
+            }
+        }
+
+        client
+    }
+}
+
+impl FromStr for TrustedProxies {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            networks: s
+                .split(',')
+                .filter(|x| !x.trim().is_empty())
+                .map(Network::from_str)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// The address of the client that made the request, available as an extension to the handlers and
+/// middlewares. It is `None` only if the request came through a Unix socket without `X-Forwarded-For`.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+pub async fn middleware<B: Send>(
+    State(trusted_proxies): State<Arc<TrustedProxies>>,
+    ConnectInfo(peer): ConnectInfo<PeerAddress>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let forwarded_for = request
+        .headers()
+        .get("X-Forwarded-For")
+        .and_then(|x| x.to_str().ok());
+    let client_ip = trusted_proxies.resolve(peer.0, forwarded_for);
+
+    request.extensions_mut().insert(ClientIp(client_ip));
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    pub fn can_match_networks() {
+        let proxies: TrustedProxies = "10.0.0.0/8, 192.168.1.1, fd00::/8".parse().unwrap();
+
+        assert!(proxies.contains(ip("10.1.2.3")));
+        assert!(proxies.contains(ip("192.168.1.1")));
+        assert!(proxies.contains(ip("fd12::1")));
+        assert!(!proxies.contains(ip("11.0.0.1")));
+        assert!(!proxies.contains(ip("192.168.1.2")));
+        assert!(!proxies.contains(ip("fe80::1")));
+    }
+
+    #[test]
+    pub fn rejects_invalid_networks() {
+        assert!("10.0.0.0/33".parse::<TrustedProxies>().is_err());
+        assert!("10.0.0/8".parse::<TrustedProxies>().is_err());
+        assert!("::/129".parse::<TrustedProxies>().is_err());
+    }
+
+    #[test]
+    pub fn ignores_forwarded_for_from_untrusted_peers() {
+        let proxies: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+
+        assert_eq!(
+            Some(ip("1.2.3.4")),
+            proxies.resolve(Some(ip("1.2.3.4")), Some("5.6.7.8"))
+        );
+    }
+
+    #[test]
+    pub fn uses_the_first_untrusted_forwarded_address() {
+        let proxies: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+
+        assert_eq!(
+            Some(ip("5.6.7.8")),
+            proxies.resolve(Some(ip("10.0.0.1")), Some("1.1.1.1, 5.6.7.8, 10.0.0.2"))
+        );
+        assert_eq!(
+            Some(ip("10.0.0.1")),
+            proxies.resolve(Some(ip("10.0.0.1")), None)
+        );
+        assert_eq!(Some(ip("5.6.7.8")), proxies.resolve(None, Some("5.6.7.8")));
+    }
+}
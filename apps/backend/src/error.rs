@@ -0,0 +1,390 @@
+use std::time::Duration;
+
+use askama::Template;
+use axum::{
+    extract::rejection::{FormRejection, JsonRejection, PathRejection, QueryRejection},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use thiserror::Error;
+use tracing::Span;
+use webauthn_rs::prelude::WebauthnError;
+
+use crate::{
+    blog::posts,
+    security_headers,
+    service_accounts::{self, RepositoryError},
+    users,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Not found")]
+    NotFound,
+    #[error("Validation failed: {0}")]
+    Validation(String),
+    #[error("Unauthorized")]
+    Unauthorized,
+    /// A signed request made with a token issued before the requests were signed
+    #[error("Unauthorized: the token has no signing key")]
+    NoSigningKey,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Too many requests, retry after {retry_after:?}")]
+    TooManyRequests { retry_after: Duration },
+    #[error("Database Error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("Posts repository error: {0}")]
+    Posts(#[from] posts::Error),
+    #[error("Service accounts repository error: {0}")]
+    ServiceAccounts(#[from] service_accounts::RepositoryError),
+    #[error("Users repository error: {0}")]
+    Users(#[from] users::Error),
+    #[error("Failed to render template: {0}")]
+    Template(#[from] askama::Error),
+    #[error("WebAuthn error: {0}")]
+    Webauthn(#[from] WebauthnError),
+}
+
+impl From<JsonRejection> for Error {
+    fn from(value: JsonRejection) -> Self {
+        Self::Validation(value.body_text())
+    }
+}
+
+impl From<FormRejection> for Error {
+    fn from(value: FormRejection) -> Self {
+        Self::Validation(value.body_text())
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(value: QueryRejection) -> Self {
+        Self::Validation(value.body_text())
+    }
+}
+
+impl From<PathRejection> for Error {
+    fn from(_: PathRejection) -> Self {
+        Self::NotFound
+    }
+}
+
+impl Error {
+    const fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Unauthorized | Self::NoSigningKey => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Conflict(_)
+            | Self::Posts(posts::Error::DuplicateSlug(_))
+            | Self::Users(users::Error::DuplicateName(_))
+            | Self::ServiceAccounts(
+                RepositoryError::ConcurrentModification(_) | RepositoryError::DuplicateName(_),
+            ) => StatusCode::CONFLICT,
+            Self::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Sqlx(_)
+            | Self::Posts(posts::Error::Sqlx(_))
+            | Self::ServiceAccounts(RepositoryError::Sqlx(_))
+            | Self::Users(users::Error::Sqlx(_))
+            | Self::Template(_)
+            | Self::Webauthn(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    const fn kind(&self) -> &'static str {
+        match self {
+            Self::NotFound => "not_found",
+            Self::Validation(_) => "validation",
+            Self::Unauthorized => "unauthorized",
+            Self::NoSigningKey => "no_signing_key",
+            Self::Forbidden(_) => "forbidden",
+            Self::Conflict(_) => "conflict",
+            Self::TooManyRequests { .. } => "too_many_requests",
+            Self::Sqlx(_) => "database",
+            Self::Posts(_) => "posts_repository",
+            Self::ServiceAccounts(_) => "service_accounts_repository",
+            Self::Users(_) => "users_repository",
+            Self::Template(_) => "template",
+            Self::Webauthn(_) => "webauthn",
+        }
+    }
+
+    /// The message that can be shown to the client, internal details are only sent to tracing.
+    fn public_detail(&self) -> Option<String> {
+        match self {
+            Self::Validation(message) | Self::Forbidden(message) | Self::Conflict(message) => {
+                Some(message.clone())
+            }
+            Self::ServiceAccounts(
+                e
+                @ (RepositoryError::ConcurrentModification(_) | RepositoryError::DuplicateName(_)),
+            ) => Some(e.to_string()),
+            Self::Posts(e @ posts::Error::DuplicateSlug(_)) => Some(e.to_string()),
+            Self::Users(e @ users::Error::DuplicateName(_)) => Some(e.to_string()),
+            Self::NoSigningKey => {
+                Some("This token predates request signing, rotate it or use --no-sign".to_string())
+            }
+            Self::NotFound
+            | Self::Unauthorized
+            | Self::TooManyRequests { .. }
+            | Self::Sqlx(_)
+            | Self::Posts(posts::Error::Sqlx(_))
+            | Self::ServiceAccounts(RepositoryError::Sqlx(_))
+            | Self::Users(users::Error::Sqlx(_))
+            | Self::Template(_)
+            | Self::Webauthn(_) => None,
+        }
+    }
+
+    fn add_headers(&self, headers: &mut HeaderMap) {
+        if let Self::TooManyRequests { retry_after } = self {
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+    }
+
+    fn record_on_span(&self) {
+        let span = Span::current();
+        let status = self.status();
+
+        span.record("error.type", self.kind());
+        span.record("exception.message", self.to_string());
+
+        if status.is_server_error() {
+            span.record("otel.status_code", "ERROR");
+            tracing::error!(error_kind = self.kind(), "{}", self);
+        } else {
+            tracing::info!(error_kind = self.kind(), "{}", self);
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    status: u16,
+    title: String,
+    detail: Option<String>,
+    csp_nonce: String,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        self.record_on_span();
+
+        let status = self.status();
+        let template = ErrorTemplate {
+            status: status.as_u16(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            detail: self.public_detail(),
+            csp_nonce: security_headers::nonce(),
+        };
+
+        let mut response = match template.render() {
+            Ok(html) => (status, Html(html)).into_response(),
+            Err(e) => {
+                tracing::error!("Failed to render error page: {}", e);
+
+                (status, template.title).into_response()
+            }
+        };
+        self.add_headers(response.headers_mut());
+
+        response
+    }
+}
+
+/// RFC 9457 problem details, returned from the `/api` routes.
+#[derive(Serialize)]
+struct ProblemDetails {
+    #[serde(rename = "type")]
+    kind: String,
+    title: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+/// Wraps [`Error`] so that it is rendered as problem details JSON instead of an HTML page.
+#[derive(Debug)]
+pub struct ApiError(Error);
+
+impl<T> From<T> for ApiError
+where
+    T: Into<Error>,
+{
+    fn from(value: T) -> Self {
+        Self(value.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        self.0.record_on_span();
+
+        let status = self.0.status();
+        let problem = ProblemDetails {
+            kind: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: self.0.public_detail(),
+        };
+
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            Json(problem),
+        )
+            .into_response();
+        self.0.add_headers(response.headers_mut());
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn maps_errors_to_statuses_and_kinds() {
+        let cases = [
+            (Error::NotFound, StatusCode::NOT_FOUND, "not_found"),
+            (
+                Error::Validation("title".to_string()),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "validation",
+            ),
+            (
+                Error::Unauthorized,
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ),
+            (
+                Error::NoSigningKey,
+                StatusCode::UNAUTHORIZED,
+                "no_signing_key",
+            ),
+            (
+                Error::Forbidden("scope".to_string()),
+                StatusCode::FORBIDDEN,
+                "forbidden",
+            ),
+            (
+                Error::Posts(posts::Error::DuplicateSlug("hello".to_string())),
+                StatusCode::CONFLICT,
+                "posts_repository",
+            ),
+            (
+                Error::ServiceAccounts(RepositoryError::DuplicateName("ci".to_string())),
+                StatusCode::CONFLICT,
+                "service_accounts_repository",
+            ),
+            (
+                Error::Users(users::Error::DuplicateName("ramona".to_string())),
+                StatusCode::CONFLICT,
+                "users_repository",
+            ),
+            (
+                Error::TooManyRequests {
+                    retry_after: Duration::from_secs(1),
+                },
+                StatusCode::TOO_MANY_REQUESTS,
+                "too_many_requests",
+            ),
+            (
+                Error::Sqlx(sqlx::Error::RowNotFound),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "database",
+            ),
+            (
+                Error::ServiceAccounts(RepositoryError::Sqlx(sqlx::Error::PoolTimedOut)),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "service_accounts_repository",
+            ),
+        ];
+
+        for (error, status, kind) in cases {
+            assert_eq!(status, error.status(), "{error}");
+            assert_eq!(kind, error.kind(), "{error}");
+        }
+    }
+
+    #[test]
+    pub fn hides_internal_details() {
+        assert_eq!(None, Error::Sqlx(sqlx::Error::PoolTimedOut).public_detail());
+        assert_eq!(
+            None,
+            Error::Posts(posts::Error::Sqlx(sqlx::Error::PoolTimedOut)).public_detail()
+        );
+        assert_eq!(None, Error::Unauthorized.public_detail());
+
+        assert_eq!(
+            Some("title is required".to_string()),
+            Error::Validation("title is required".to_string()).public_detail()
+        );
+        assert!(Error::NoSigningKey
+            .public_detail()
+            .is_some_and(|x| x.contains("--no-sign")));
+    }
+
+    #[test]
+    pub fn rounds_retry_after_up() {
+        let mut headers = HeaderMap::new();
+        Error::TooManyRequests {
+            retry_after: Duration::from_millis(1500),
+        }
+        .add_headers(&mut headers);
+        assert_eq!("2", headers[header::RETRY_AFTER]);
+
+        let mut headers = HeaderMap::new();
+        Error::TooManyRequests {
+            retry_after: Duration::from_secs(3),
+        }
+        .add_headers(&mut headers);
+        assert_eq!("3", headers[header::RETRY_AFTER]);
+
+        let mut headers = HeaderMap::new();
+        Error::NotFound.add_headers(&mut headers);
+        assert!(headers.is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn api_errors_are_problem_details() {
+        let response =
+            ApiError::from(Error::Conflict("the post was changed".to_string())).into_response();
+
+        assert_eq!(StatusCode::CONFLICT, response.status());
+        assert_eq!(
+            "application/problem+json",
+            response.headers()[header::CONTENT_TYPE]
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Conflict",
+                "status": 409,
+                "detail": "the post was changed",
+            }),
+            body
+        );
+
+        let response = ApiError::from(Error::Sqlx(sqlx::Error::PoolTimedOut)).into_response();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(None, body.get("detail"));
+        assert_eq!(500, body["status"]);
+    }
+}
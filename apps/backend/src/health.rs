@@ -0,0 +1,281 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use serde::Serialize;
+use sqlx::{migrate::Migrator, Pool, Postgres};
+
+use crate::shutdown::Shutdown;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/");
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Health {
+    db_pool: Arc<Pool<Postgres>>,
+    shutdown: Shutdown,
+    started_at: Instant,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Ok,
+    Unavailable,
+}
+
+#[derive(Serialize)]
+struct DatabaseCheck {
+    status: Status,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MigrationsCheck {
+    status: Status,
+    expected_version: Option<i64>,
+    applied_version: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Checks {
+    database: DatabaseCheck,
+    migrations: MigrationsCheck,
+}
+
+#[derive(Serialize)]
+struct Report {
+    status: Status,
+    version: &'static str,
+    uptime_seconds: u64,
+    shutting_down: bool,
+    database_connections: u32,
+    database_idle_connections: usize,
+    checks: Checks,
+}
+
+impl Health {
+    pub fn new(db_pool: Arc<Pool<Postgres>>, shutdown: Shutdown) -> Self {
+        Self {
+            db_pool,
+            shutdown,
+            started_at: Instant::now(),
+        }
+    }
+
+    async fn check_database(&self) -> DatabaseCheck {
+        let start = Instant::now();
+        let result = tokio::time::timeout(
+            CHECK_TIMEOUT,
+            sqlx::query!("SELECT 1 AS alive").fetch_one(self.db_pool.as_ref()),
+        )
+        .await;
+
+        let error = match result {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("Query timed out after {CHECK_TIMEOUT:?}")),
+        };
+
+        DatabaseCheck {
+            status: if error.is_none() {
+                Status::Ok
+            } else {
+                Status::Unavailable
+            },
+            latency_ms: start.elapsed().as_millis(),
+            error,
+        }
+    }
+
+    async fn check_migrations(&self) -> MigrationsCheck {
+        let expected_version = MIGRATOR.iter().map(|x| x.version).max();
+        let result = tokio::time::timeout(
+            CHECK_TIMEOUT,
+            sqlx::query_scalar!("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+                .fetch_one(self.db_pool.as_ref()),
+        )
+        .await;
+
+        let (applied_version, error) = match result {
+            Ok(Ok(version)) => (version, None),
+            Ok(Err(e)) => (None, Some(e.to_string())),
+            Err(_) => (
+                None,
+                Some(format!("Query timed out after {CHECK_TIMEOUT:?}")),
+            ),
+        };
+
+        MigrationsCheck {
+            status: migrations_status(error.is_none(), applied_version, expected_version),
+            expected_version,
+            applied_version,
+            error,
+        }
+    }
+
+    async fn report(&self) -> Report {
+        let (database, migrations) = tokio::join!(self.check_database(), self.check_migrations());
+        let shutting_down = self.shutdown.is_shutting_down();
+        let checks = Checks {
+            database,
+            migrations,
+        };
+
+        Report {
+            status: overall_status(shutting_down, &checks),
+            version: env!("CARGO_PKG_VERSION"),
+            uptime_seconds: self.started_at.elapsed().as_secs(),
+            shutting_down,
+            database_connections: self.db_pool.size(),
+            database_idle_connections: self.db_pool.num_idle(),
+            checks,
+        }
+    }
+}
+
+/// The database can be ahead of the migrations this version knows, e.g. while a newer version is
+/// being rolled out.
+fn migrations_status(
+    succeeded: bool,
+    applied_version: Option<i64>,
+    expected_version: Option<i64>,
+) -> Status {
+    if succeeded && applied_version >= expected_version {
+        Status::Ok
+    } else {
+        Status::Unavailable
+    }
+}
+
+fn overall_status(shutting_down: bool, checks: &Checks) -> Status {
+    if shutting_down
+        || checks.database.status != Status::Ok
+        || checks.migrations.status != Status::Ok
+    {
+        Status::Unavailable
+    } else {
+        Status::Ok
+    }
+}
+
+const fn status_code(status: Status) -> StatusCode {
+    match status {
+        Status::Ok => StatusCode::OK,
+        Status::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Liveness, the process is up and able to answer requests.
+pub async fn route_healthz() -> impl IntoResponse {
+    "ok"
+}
+
+/// Readiness, the process can do useful work and should receive traffic.
+pub async fn route_readyz(State(health): State<Arc<Health>>) -> impl IntoResponse {
+    let status = health.report().await.status;
+
+    (
+        status_code(status),
+        match status {
+            Status::Ok => "ok",
+            Status::Unavailable => "unavailable",
+        },
+    )
+}
+
+/// A detailed report for operators, with the errors of the checks, so it's only served on the
+/// admin listeners.
+pub async fn route_health(State(health): State<Arc<Health>>) -> impl IntoResponse {
+    let report = health.report().await;
+
+    (status_code(report.status), Json(report))
+}
+
+/// The liveness and readiness probes, which tell nothing but whether the checks pass.
+pub fn probes_router(health: Arc<Health>) -> Router {
+    Router::new()
+        .route("/healthz", get(route_healthz))
+        .route("/readyz", get(route_readyz))
+        .with_state(health)
+}
+
+/// The probes, and the detailed report.
+pub fn router(health: Arc<Health>) -> Router {
+    probes_router(health.clone()).merge(
+        Router::new()
+            .route("/health", get(route_health))
+            .with_state(health),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn checks(database: Status, migrations: Status) -> Checks {
+        Checks {
+            database: DatabaseCheck {
+                status: database,
+                latency_ms: 1,
+                error: None,
+            },
+            migrations: MigrationsCheck {
+                status: migrations,
+                expected_version: Some(2),
+                applied_version: Some(2),
+                error: None,
+            },
+        }
+    }
+
+    #[test]
+    pub fn ready_only_when_every_check_passes() {
+        assert_eq!(
+            Status::Ok,
+            overall_status(false, &checks(Status::Ok, Status::Ok))
+        );
+        assert_eq!(
+            Status::Unavailable,
+            overall_status(true, &checks(Status::Ok, Status::Ok))
+        );
+        assert_eq!(
+            Status::Unavailable,
+            overall_status(false, &checks(Status::Unavailable, Status::Ok))
+        );
+        assert_eq!(
+            Status::Unavailable,
+            overall_status(false, &checks(Status::Ok, Status::Unavailable))
+        );
+    }
+
+    #[test]
+    pub fn migrations_have_to_be_applied() {
+        assert_eq!(Status::Ok, migrations_status(true, Some(2), Some(2)));
+        assert_eq!(Status::Ok, migrations_status(true, Some(3), Some(2)));
+        assert_eq!(
+            Status::Unavailable,
+            migrations_status(true, Some(1), Some(2))
+        );
+        assert_eq!(Status::Unavailable, migrations_status(true, None, Some(2)));
+        assert_eq!(
+            Status::Unavailable,
+            migrations_status(false, Some(2), Some(2))
+        );
+    }
+
+    #[test]
+    pub fn unavailable_is_503() {
+        assert_eq!(StatusCode::OK, status_code(Status::Ok));
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            status_code(Status::Unavailable)
+        );
+    }
+}
@@ -2,24 +2,42 @@
 
 use std::{sync::Arc, time::Duration};
 
-use ::tracing::{Level, Span};
+use ::tracing::{field::Empty, Level, Span};
 use axum::{
     body::{Body, BoxBody},
-    extract::MatchedPath,
+    extract::{DefaultBodyLimit, MatchedPath},
+    handler::HandlerWithoutStateExt,
     http::{uri::Scheme, Request, Response},
     routing::{get, post},
     Router,
 };
+use health::Health;
 use rand::thread_rng;
-use service_accounts::ServiceAccountRepository;
+use rate_limit::RateLimiter;
+use service_accounts::{
+    jwt::{self, JwtValidator},
+    Authenticator, NonceStore, Pepper, ServiceAccountRepository, TokenOutput, TokenUsage,
+};
+use shutdown::Shutdown;
+use sqlx::{Pool, Postgres};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tracing::setup_tracing_subscriber;
 
 use crate::service_accounts::initialize_root_account;
 
+mod audit;
+mod client_ip;
 mod database;
+mod error;
+mod health;
+mod rate_limit;
 mod secrets;
+mod security_headers;
+mod server;
 mod service_accounts;
+mod shutdown;
 mod tracing;
+mod users;
 
 mod blog;
 
@@ -45,7 +63,12 @@ fn make_span(request: &Request<Body>) -> Span {
         network.protocol.name = request.uri().scheme().map_or("http", Scheme::as_str),
         http.request.body.size = content_length,
         http.request.method = request.method().as_str(),
-        user_agent.original = user_agent
+        user_agent.original = user_agent,
+        http.response.status_code = Empty,
+        http.response.body.size = Empty,
+        "error.type" = Empty,
+        exception.message = Empty,
+        otel.status_code = Empty
     )
 }
 
@@ -60,58 +83,234 @@ fn on_response(response: &Response<BoxBody>, _latency: Duration, span: &Span) {
     span.record("http.response.body.size", content_length);
 }
 
-#[tokio::main]
-async fn main() {
-    #[cfg(debug_assertions)]
-    {
-        dotenvy::dotenv().expect("Failed to load .env");
-    }
+/// Also starts the background tasks of the authentication, the token usage has to be flushed once
+/// the server stops.
+async fn start_authenticator(
+    db_pool: &Arc<Pool<Postgres>>,
+    service_account_repository: Arc<ServiceAccountRepository>,
+) -> (Arc<Authenticator>, Arc<TokenUsage>) {
+    let token_usage = Arc::new(TokenUsage::new(db_pool.clone()));
+    tokio::spawn(token_usage.clone().run());
 
-    setup_tracing_subscriber().expect("Failed to setup tracing!");
+    let nonces = Arc::new(NonceStore::new(db_pool.clone()));
+    tokio::spawn(nonces.clone().run());
 
-    let db_pool = database::connect(database::AccessLevel::App)
-        .await
-        .expect("Database connection failed");
-    let db_pool = Arc::new(db_pool);
+    let jwt = match jwt::Config::from_env().expect("Invalid JWT configuration") {
+        Some(config) => {
+            let validator = Arc::new(
+                JwtValidator::load(config)
+                    .await
+                    .expect("Failed to load the JWT key set"),
+            );
+            tokio::spawn(validator.clone().run());
 
-    let service_account_repository = Arc::new(ServiceAccountRepository::new(db_pool.clone()));
-    initialize_root_account(service_account_repository.clone(), thread_rng)
-        .await
-        .expect("Failed to init root account");
+            Some(validator)
+        }
+        None => None,
+    };
 
-    let asset_path = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "dist".to_string());
+    let authenticator = Arc::new(Authenticator::new(
+        service_account_repository,
+        token_usage.clone(),
+        nonces,
+        jwt,
+    ));
 
-    let assets_service = tower_http::services::ServeDir::new(asset_path);
+    (authenticator, token_usage)
+}
 
+fn api_router(
+    db_pool: &Arc<Pool<Postgres>>,
+    service_account_repository: Arc<ServiceAccountRepository>,
+    authenticator: Arc<Authenticator>,
+) -> Router {
     let blog_repository = Arc::new(blog::posts::Repository::new(db_pool.clone()));
-    let blog = blog::Blog::new(db_pool.clone());
-    let blog = Arc::new(blog);
+    let audit_log = Arc::new(audit::AuditLog::new(db_pool.clone()));
 
-    let api = Router::new()
-        .route("/posts", post(blog::route_api_post_posts))
+    let api_rate_limiter = Arc::new(RateLimiter::new(
+        rate_limit::Config::from_env("API", rate_limit::Config::new(20, 2.0))
+            .expect("Invalid API rate limit"),
+    ));
+    let service_account_rate_limiter = Arc::new(RateLimiter::new(
+        rate_limit::Config::from_env("SERVICE_ACCOUNT", rate_limit::Config::new(100, 20.0))
+            .expect("Invalid service account rate limit"),
+    ));
+
+    blog::api::router(blog_repository)
+        .merge(blog::media::api_router(Arc::new(
+            blog::media::Repository::new(db_pool.clone()),
+        )))
+        .layer(axum::middleware::from_fn_with_state(
+            service_account_rate_limiter,
+            rate_limit::service_account_middleware,
+        ))
+        .merge(service_accounts::api::router(service_account_repository))
+        .merge(users::api::router(Arc::new(users::UserRepository::new(
+            db_pool.clone(),
+        ))))
+        .merge(audit::router(audit_log.clone()))
+        .layer(axum::middleware::from_fn_with_state(
+            audit_log,
+            audit::middleware,
+        ))
         .layer(axum::middleware::from_fn_with_state(
-            service_account_repository,
+            authenticator,
             service_accounts::middleware,
         ))
-        .with_state(blog_repository.clone());
+        .layer(axum::middleware::from_fn_with_state(
+            api_rate_limiter,
+            rate_limit::api_client_ip_middleware,
+        ))
+}
 
-    let application = Router::new()
+fn admin_router(db_pool: &Arc<Pool<Postgres>>) -> Router {
+    let sessions = Arc::new(users::SessionStore::new(db_pool.clone()));
+    let auth = users::auth::PasskeyAuth::from_env(
+        db_pool.clone(),
+        Arc::new(users::UserRepository::new(db_pool.clone())),
+        sessions.clone(),
+    )
+    .expect("Invalid WebAuthn configuration");
+
+    users::auth::router(Arc::new(auth)).merge(blog::admin::router(
+        Arc::new(blog::posts::Repository::new(db_pool.clone())),
+        sessions,
+        Arc::new(audit::AuditLog::new(db_pool.clone())),
+    ))
+}
+
+fn application_router(
+    db_pool: &Arc<Pool<Postgres>>,
+    api: Router,
+    admin: Router,
+    asset_path: String,
+) -> Router {
+    let assets_service = tower_http::services::ServeDir::new(asset_path)
+        .not_found_service(blog::route_not_found.into_service());
+
+    let blog = Arc::new(blog::Blog::new(db_pool.clone()));
+
+    let trusted_proxies =
+        Arc::new(client_ip::TrustedProxies::from_env().expect("Invalid TRUSTED_PROXIES"));
+    let public_rate_limiter = Arc::new(RateLimiter::new(
+        rate_limit::Config::from_env("PUBLIC", rate_limit::Config::new(60, 10.0))
+            .expect("Invalid public rate limit"),
+    ));
+
+    Router::new()
         .route("/", get(blog::route_main))
         .route("/posts/:id", get(blog::route_posts_id))
+        .route(
+            security_headers::CSP_REPORT_PATH,
+            post(security_headers::route_csp_report).layer(DefaultBodyLimit::max(64 * 1024)),
+        )
         .with_state(blog)
+        .merge(blog::media::router(Arc::new(blog::media::Repository::new(
+            db_pool.clone(),
+        ))))
+        .merge(admin)
+        .layer(axum::middleware::from_fn_with_state(
+            public_rate_limiter,
+            rate_limit::client_ip_middleware,
+        ))
         .nest("/api", api)
         .fallback_service(assets_service)
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(
+                security_headers::Config::from_env()
+                    .expect("Invalid security headers configuration"),
+            ),
+            security_headers::middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            trusted_proxies,
+            client_ip::middleware,
+        ))
         .layer(
             tower_http::trace::TraceLayer::new_for_http()
                 .make_span_with(make_span)
                 .on_request(on_request)
                 .on_response(on_response),
-        );
+        )
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
 
-    axum::Server::bind(&"0.0.0.0:8080".parse().unwrap())
-        .serve(application.into_make_service())
+#[tokio::main]
+async fn main() {
+    #[cfg(debug_assertions)]
+    {
+        dotenvy::dotenv().expect("Failed to load .env");
+    }
+
+    setup_tracing_subscriber().expect("Failed to setup tracing!");
+
+    let shutdown = Shutdown::new();
+    tokio::spawn(shutdown.clone().trigger_on_signal());
+
+    let db_pool = database::connect(database::AccessLevel::App)
         .await
-        .unwrap();
+        .expect("Database connection failed");
+    let db_pool = Arc::new(db_pool);
+
+    let pepper = Pepper::from_secret().expect("Failed to read the request signing pepper");
+    let service_account_repository = Arc::new(ServiceAccountRepository::new(
+        db_pool.clone(),
+        Arc::new(pepper),
+    ));
+    let root_token_output = TokenOutput::from_env().expect("Invalid ROOT_TOKEN_OUTPUT");
+    initialize_root_account(
+        service_account_repository.clone(),
+        &root_token_output,
+        thread_rng,
+    )
+    .await
+    .expect("Failed to init root account");
+
+    let asset_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "dist".to_string());
+
+    let (authenticator, token_usage) =
+        start_authenticator(&db_pool, service_account_repository.clone()).await;
+    let api = api_router(&db_pool, service_account_repository, authenticator);
+    let application = application_router(&db_pool, api, admin_router(&db_pool), asset_path);
+
+    let listeners = server::listeners_from_env().expect("Invalid listener configuration");
+    let admin_listeners =
+        server::admin_listeners_from_env().expect("Invalid admin listener configuration");
+    let shutdown_timeout =
+        server::shutdown_timeout_from_env().expect("Invalid shutdown timeout configuration");
+
+    // The health endpoints are merged after the trace layer, so that probes don't end up in traces
+    let health = Arc::new(Health::new(db_pool.clone(), shutdown.clone()));
+
+    if admin_listeners.is_empty() {
+        // The detailed report has the errors of the checks, it's not public
+        server::serve(
+            application.merge(health::probes_router(health)),
+            listeners,
+            shutdown,
+            shutdown_timeout,
+        )
+        .await
+        .expect("Server failed");
+    } else {
+        tokio::try_join!(
+            server::serve(application, listeners, shutdown.clone(), shutdown_timeout),
+            server::serve(
+                health::router(health),
+                admin_listeners,
+                shutdown,
+                shutdown_timeout
+            )
+        )
+        .expect("Server failed");
+    }
+
+    if let Err(e) = token_usage.flush().await {
+        ::tracing::warn!("Failed to save token usage: {}", e);
+    }
+
+    tracing::shutdown().await;
 }
@@ -0,0 +1,260 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{extract::State, http::Request, middleware::Next, response::Response};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    client_ip::ClientIp,
+    error::{ApiError, Error},
+    service_accounts::Authenticated,
+};
+
+const SWEEP_INTERVAL: Duration = Duration::from_mins(1);
+/// Past this, the keys without a bucket are limited until the next sweep, so that the memory use is
+/// bounded.
+const MAX_BUCKETS: usize = 100_000;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// How many requests can be made at once, after the bucket had time to fill up
+    burst: u32,
+    /// How many requests per second are allowed in the long run
+    per_second: f64,
+}
+
+impl Config {
+    pub const fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+
+    /// Reads `RATE_LIMIT_<NAME>_BURST` and `RATE_LIMIT_<NAME>_PER_SECOND`, falling back to `default`
+    /// for the ones that are not set.
+    pub fn from_env(name: &str, default: Self) -> Result<Self, ConfigError> {
+        fn read<T: std::str::FromStr>(variable: String, default: T) -> Result<T, ConfigError> {
+            std::env::var(&variable).map_or(Ok(default), |value| {
+                value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(variable, value))
+            })
+        }
+
+        let burst = read(format!("RATE_LIMIT_{name}_BURST"), default.burst)?;
+        let per_second = read(format!("RATE_LIMIT_{name}_PER_SECOND"), default.per_second)?;
+
+        if burst == 0 || per_second.is_nan() || per_second <= 0.0 {
+            return Err(ConfigError::InvalidValue(
+                format!("RATE_LIMIT_{name}"),
+                format!("{burst} burst, {per_second} per second"),
+            ));
+        }
+
+        Ok(Self { burst, per_second })
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, config: Config, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+
+        self.tokens = elapsed
+            .mul_add(config.per_second, self.tokens)
+            .min(f64::from(config.burst));
+        self.updated_at = now;
+    }
+}
+
+/// A token bucket rate limiter, with a separate bucket for every key.
+pub struct RateLimiter<TKey> {
+    config: Config,
+    state: Mutex<(HashMap<TKey, Bucket>, Instant)>,
+}
+
+impl<TKey: Hash + Eq> RateLimiter<TKey> {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            state: Mutex::new((HashMap::new(), Instant::now())),
+        }
+    }
+
+    /// Takes a token from the key's bucket, or returns how long it will take for one to be available.
+    fn check_at(&self, key: TKey, now: Instant) -> Result<(), Duration> {
+        let (buckets, last_sweep) = &mut *self.state.lock().unwrap();
+
+        // Full buckets behave exactly like missing ones, so there's no need to keep them
+        if now.saturating_duration_since(*last_sweep) > SWEEP_INTERVAL {
+            buckets.retain(|_, bucket| {
+                bucket.refill(self.config, now);
+                bucket.tokens < f64::from(self.config.burst)
+            });
+            *last_sweep = now;
+        }
+
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&key) {
+            return Err(Duration::from_secs_f64(1.0 / self.config.per_second));
+        }
+
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: f64::from(self.config.burst),
+            updated_at: now,
+        });
+        bucket.refill(self.config, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64(
+            (1.0 - bucket.tokens) / self.config.per_second,
+        ))
+    }
+
+    fn check(&self, key: TKey) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+}
+
+/// The clients without an IP, like the ones on a Unix socket without `X-Forwarded-For`, can't be
+/// told apart, they would all share one bucket, so they are not limited.
+fn check_client_ip<B>(limiter: &RateLimiter<IpAddr>, request: &Request<B>) -> Result<(), Error> {
+    let Some(client_ip) = request.extensions().get::<ClientIp>().and_then(|x| x.0) else {
+        return Ok(());
+    };
+
+    limiter
+        .check(client_ip)
+        .map_err(|retry_after| Error::TooManyRequests { retry_after })
+}
+
+/// Limits the requests by client IP, for the browser routes.
+pub async fn client_ip_middleware<B: Send>(
+    State(limiter): State<Arc<RateLimiter<IpAddr>>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, Error> {
+    check_client_ip(&limiter, &request)?;
+
+    Ok(next.run(request).await)
+}
+
+/// Limits the requests by client IP, for the API routes. This must run before authentication, so
+/// that guessing tokens is limited too.
+pub async fn api_client_ip_middleware<B: Send>(
+    State(limiter): State<Arc<RateLimiter<IpAddr>>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    check_client_ip(&limiter, &request)?;
+
+    Ok(next.run(request).await)
+}
+
+/// Limits the requests by the authenticated service account.
+pub async fn service_account_middleware<B: Send>(
+    State(limiter): State<Arc<RateLimiter<Uuid>>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    if let Some(authenticated) = request.extensions().get::<Authenticated>() {
+        limiter
+            .check(authenticated.account().id())
+            .map_err(|retry_after| Error::TooManyRequests { retry_after })?;
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    pub fn allows_a_burst_and_then_limits() {
+        let limiter = RateLimiter::new(Config::new(3, 1.0));
+        let now = Instant::now();
+
+        assert!(limiter.check_at("a", now).is_ok());
+        assert!(limiter.check_at("a", now).is_ok());
+        assert!(limiter.check_at("a", now).is_ok());
+        assert_eq!(Err(Duration::from_secs(1)), limiter.check_at("a", now));
+
+        assert!(limiter.check_at("b", now).is_ok());
+    }
+
+    #[test]
+    pub fn refills_over_time() {
+        let limiter = RateLimiter::new(Config::new(1, 2.0));
+        let now = Instant::now();
+
+        assert!(limiter.check_at("a", now).is_ok());
+        assert_eq!(
+            Err(Duration::from_millis(250)),
+            limiter.check_at("a", now + Duration::from_millis(250))
+        );
+        assert!(limiter
+            .check_at("a", now + Duration::from_millis(500))
+            .is_ok());
+    }
+
+    #[test]
+    pub fn removes_full_buckets() {
+        let limiter = RateLimiter::new(Config::new(2, 1.0));
+        let now = Instant::now();
+
+        limiter.check_at("a", now).unwrap();
+        limiter
+            .check_at("b", now + SWEEP_INTERVAL + Duration::from_millis(500))
+            .unwrap();
+        limiter
+            .check_at("c", now + SWEEP_INTERVAL + Duration::from_secs(1))
+            .unwrap();
+
+        let mut keys: Vec<_> = limiter.state.lock().unwrap().0.keys().copied().collect();
+        keys.sort_unstable();
+
+        assert_eq!(vec!["b", "c"], keys);
+    }
+
+    #[test]
+    pub fn limits_the_number_of_buckets() {
+        let limiter = RateLimiter::new(Config::new(2, 1.0));
+        let now = Instant::now();
+
+        for key in 0..MAX_BUCKETS {
+            limiter.check_at(key, now).unwrap();
+        }
+
+        assert_eq!(
+            Err(Duration::from_secs(1)),
+            limiter.check_at(MAX_BUCKETS, now)
+        );
+        assert!(limiter.check_at(0, now).is_ok());
+        // The sweep makes room
+        assert!(limiter
+            .check_at(MAX_BUCKETS, now + SWEEP_INTERVAL * 2)
+            .is_ok());
+    }
+}
@@ -0,0 +1,219 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::{thread_rng, RngCore};
+use thiserror::Error;
+use tracing::warn;
+
+pub const CSP_REPORT_PATH: &str = "/csp-report";
+
+const MAX_LOGGED_REPORT_SIZE: usize = 4096;
+
+tokio::task_local! {
+    static NONCE: String;
+}
+
+/// The CSP nonce of the request that is currently being handled. It has to be set on every inline
+/// `<script>` and `<style>` element, otherwise the browser won't run them.
+pub fn nonce() -> String {
+    NONCE.try_with(Clone::clone).unwrap_or_default()
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Invalid value for HSTS_MAX_AGE: {0}")]
+    InvalidHstsMaxAge(String),
+}
+
+pub struct Config {
+    report_only: bool,
+    /// The `Strict-Transport-Security` value, if any
+    hsts: Option<String>,
+}
+
+fn is_enabled(variable: &str) -> bool {
+    std::env::var(variable).is_ok_and(|x| x == "1" || x.eq_ignore_ascii_case("true"))
+}
+
+impl Config {
+    /// With `CSP_REPORT_ONLY=1`, the policy violations are only reported, not enforced.
+    ///
+    /// HSTS is off unless `HSTS_MAX_AGE` is set, in seconds, since browsers remember it and a site
+    /// that's also served over plain HTTP would become unreachable. `HSTS_INCLUDE_SUBDOMAINS=1`
+    /// extends it to all the subdomains.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let hsts = match std::env::var("HSTS_MAX_AGE") {
+            Ok(max_age) => {
+                let max_age: u64 = max_age
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidHstsMaxAge(max_age))?;
+
+                Some(if is_enabled("HSTS_INCLUDE_SUBDOMAINS") {
+                    format!("max-age={max_age}; includeSubDomains")
+                } else {
+                    format!("max-age={max_age}")
+                })
+            }
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            report_only: is_enabled("CSP_REPORT_ONLY"),
+            hsts,
+        })
+    }
+}
+
+fn content_security_policy(nonce: &str) -> String {
+    [
+        "default-src 'self'".to_string(),
+        format!("script-src 'self' 'nonce-{nonce}'"),
+        format!("style-src 'self' 'nonce-{nonce}'"),
+        "img-src 'self' data:".to_string(),
+        "font-src 'self'".to_string(),
+        "object-src 'none'".to_string(),
+        "base-uri 'self'".to_string(),
+        "form-action 'self'".to_string(),
+        "frame-ancestors 'none'".to_string(),
+        format!("report-uri {CSP_REPORT_PATH}"),
+    ]
+    .join("; ")
+}
+
+fn generate_nonce() -> String {
+    let mut nonce = [0u8; 16];
+    thread_rng().fill_bytes(&mut nonce);
+
+    STANDARD.encode(nonce)
+}
+
+pub async fn middleware<B: Send>(
+    State(config): State<Arc<Config>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let nonce = generate_nonce();
+    let csp = content_security_policy(&nonce);
+
+    let mut response = NONCE.scope(nonce, next.run(request)).await;
+
+    set_default_headers(response.headers_mut(), &config, &csp);
+
+    response
+}
+
+fn set_default_headers(headers: &mut HeaderMap, config: &Config, csp: &str) {
+    let csp_header = if config.report_only {
+        header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+    } else {
+        header::CONTENT_SECURITY_POLICY
+    };
+
+    let defaults = [
+        (csp_header, Some(csp)),
+        (header::STRICT_TRANSPORT_SECURITY, config.hsts.as_deref()),
+        (header::X_CONTENT_TYPE_OPTIONS, Some("nosniff")),
+        (
+            header::REFERRER_POLICY,
+            Some("strict-origin-when-cross-origin"),
+        ),
+        (
+            HeaderName::from_static("permissions-policy"),
+            Some(
+                "camera=(), microphone=(), geolocation=(), payment=(), usb=(), interest-cohort=()",
+            ),
+        ),
+        (header::X_FRAME_OPTIONS, Some("DENY")),
+    ];
+
+    // Handlers can set their own values, e.g. a less strict policy for a specific page
+    for (name, value) in defaults {
+        let Some(value) = value else {
+            continue;
+        };
+
+        if !headers.contains_key(&name) {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
+
+/// Receives the reports browsers send for policy violations, both in the legacy `report-uri`
+/// format and the Reporting API one, and logs them.
+pub async fn route_csp_report(body: Bytes) -> impl IntoResponse {
+    let report = String::from_utf8_lossy(&body[..body.len().min(MAX_LOGGED_REPORT_SIZE)]);
+
+    if serde_json::from_slice::<serde_json::Value>(&body).is_ok() {
+        warn!(csp.report = %report, "Content security policy violation");
+    } else {
+        warn!(csp.report = %report, "Malformed content security policy report");
+    }
+
+    axum::http::StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: Config = Config {
+        report_only: false,
+        hsts: None,
+    };
+
+    #[test]
+    pub fn policy_allows_only_the_nonce() {
+        let csp = content_security_policy("abc");
+
+        assert!(csp.contains("script-src 'self' 'nonce-abc';"));
+        assert!(csp.contains("style-src 'self' 'nonce-abc';"));
+        assert!(csp.contains("default-src 'self';"));
+        assert!(csp.contains("frame-ancestors 'none';"));
+        assert!(csp.ends_with(&format!("report-uri {CSP_REPORT_PATH}")));
+        assert!(!csp.contains("unsafe-inline"));
+    }
+
+    #[test]
+    pub fn handler_values_win_over_the_defaults() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::X_FRAME_OPTIONS,
+            HeaderValue::from_static("SAMEORIGIN"),
+        );
+
+        set_default_headers(&mut headers, &CONFIG, "default-src 'self'");
+
+        assert_eq!("SAMEORIGIN", headers[header::X_FRAME_OPTIONS]);
+        assert_eq!("nosniff", headers[header::X_CONTENT_TYPE_OPTIONS]);
+        assert_eq!(
+            "default-src 'self'",
+            headers[header::CONTENT_SECURITY_POLICY]
+        );
+    }
+
+    #[test]
+    pub fn sends_hsts_only_when_configured() {
+        let mut headers = HeaderMap::new();
+        set_default_headers(&mut headers, &CONFIG, "");
+        assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+
+        let config = Config {
+            report_only: true,
+            hsts: Some("max-age=300".to_string()),
+        };
+        let mut headers = HeaderMap::new();
+        set_default_headers(&mut headers, &config, "");
+        assert_eq!("max-age=300", headers[header::STRICT_TRANSPORT_SECURITY]);
+        assert!(headers.contains_key(header::CONTENT_SECURITY_POLICY_REPORT_ONLY));
+        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+    }
+}
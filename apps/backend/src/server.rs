@@ -0,0 +1,363 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use axum::{extract::connect_info::Connected, Router};
+use hyper::server::{accept, conn::AddrStream};
+use listenfd::ListenFd;
+use thiserror::Error;
+use tokio::{
+    net::{UnixListener, UnixStream},
+    time::Instant,
+};
+use tokio_stream::wrappers::UnixListenerStream;
+use tracing::{info, warn};
+
+use crate::shutdown::Shutdown;
+
+const DEFAULT_LISTEN_ADDRESSES: &str = "0.0.0.0:8080";
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(25);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid listen address: {0}")]
+    InvalidListenAddress(String),
+    #[error("Invalid shutdown timeout: {0}")]
+    InvalidShutdownTimeout(String),
+    #[error("Systemd socket activation requested, but no sockets were passed")]
+    NoSystemdSockets,
+    #[error("Failed to bind {0}: {1}")]
+    Bind(String, std::io::Error),
+    #[error("Server error: {0}")]
+    Server(#[from] hyper::Error),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Listener {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+    /// All the sockets passed in through systemd socket activation
+    Systemd,
+}
+
+impl FromStr for Listener {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s == "systemd" {
+            return Ok(Self::Systemd);
+        }
+
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(Error::InvalidListenAddress(s.to_string()));
+            }
+
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        let address = s.strip_prefix("tcp:").unwrap_or(s);
+
+        address
+            .parse()
+            .map(Self::Tcp)
+            .map_err(|_| Error::InvalidListenAddress(s.to_string()))
+    }
+}
+
+/// Reads the comma-separated `LISTEN_ADDRESSES` variable, where each entry is one of
+/// `<ip>:<port>`, `tcp:<ip>:<port>`, `unix:<path>` or `systemd`.
+pub fn listeners_from_env() -> Result<Vec<Listener>, Error> {
+    let addresses =
+        std::env::var("LISTEN_ADDRESSES").unwrap_or_else(|_| DEFAULT_LISTEN_ADDRESSES.to_string());
+
+    parse_listeners(&addresses)
+}
+
+fn parse_listeners(addresses: &str) -> Result<Vec<Listener>, Error> {
+    addresses
+        .split(',')
+        .filter(|x| !x.trim().is_empty())
+        .map(Listener::from_str)
+        .collect()
+}
+
+/// Reads the `ADMIN_LISTEN_ADDRESSES` variable, in the same format as `LISTEN_ADDRESSES`. When set,
+/// the operational endpoints are served only on those listeners.
+pub fn admin_listeners_from_env() -> Result<Vec<Listener>, Error> {
+    std::env::var("ADMIN_LISTEN_ADDRESSES").map_or_else(|_| Ok(vec![]), |x| parse_listeners(&x))
+}
+
+/// How long the in-flight requests have to finish after the shutdown was triggered.
+pub fn shutdown_timeout_from_env() -> Result<Duration, Error> {
+    let Ok(seconds) = std::env::var("SHUTDOWN_TIMEOUT_SECONDS") else {
+        return Ok(DEFAULT_SHUTDOWN_TIMEOUT);
+    };
+
+    seconds
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|_| Error::InvalidShutdownTimeout(seconds))
+}
+
+/// The address of the peer that opened the connection, `None` for Unix sockets.
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddress(pub Option<IpAddr>);
+
+impl Connected<&AddrStream> for PeerAddress {
+    fn connect_info(target: &AddrStream) -> Self {
+        Self(Some(target.remote_addr().ip()))
+    }
+}
+
+impl Connected<&UnixStream> for PeerAddress {
+    fn connect_info(_target: &UnixStream) -> Self {
+        Self(None)
+    }
+}
+
+enum BoundListener {
+    Tcp(std::net::TcpListener),
+    Unix(UnixListener),
+}
+
+/// A socket file left behind by a previous instance, e.g. one that was killed, makes the bind fail
+/// with `EADDRINUSE`. It's only stale when nothing listens on it anymore, a running instance keeps
+/// it. Anything else at the path is left alone, so that a typo can't delete a file.
+fn remove_stale_socket(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            match std::os::unix::net::UnixStream::connect(path) {
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    std::fs::remove_file(path)
+                }
+                Ok(_) => Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    "another process is listening on the socket",
+                )),
+                Err(e) => Err(e),
+            }
+        }
+        Ok(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "the path exists and is not a socket",
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn bind(listener: Listener) -> Result<Vec<BoundListener>, Error> {
+    match listener {
+        Listener::Tcp(address) => {
+            let listener = std::net::TcpListener::bind(address)
+                .map_err(|e| Error::Bind(address.to_string(), e))?;
+
+            info!("Listening on {}", address);
+
+            Ok(vec![BoundListener::Tcp(listener)])
+        }
+        Listener::Unix(path) => {
+            remove_stale_socket(&path).map_err(|e| Error::Bind(path.display().to_string(), e))?;
+
+            let listener = UnixListener::bind(&path)
+                .map_err(|e| Error::Bind(path.display().to_string(), e))?;
+
+            info!("Listening on unix:{}", path.display());
+
+            Ok(vec![BoundListener::Unix(listener)])
+        }
+        Listener::Systemd => {
+            let mut fds = ListenFd::from_env();
+            let mut listeners = vec![];
+
+            for index in 0..fds.len() {
+                let name = format!("systemd socket #{index}");
+
+                if let Ok(Some(listener)) = fds.take_tcp_listener(index) {
+                    info!("Listening on {}", name);
+                    listeners.push(BoundListener::Tcp(listener));
+                    continue;
+                }
+
+                let listener = fds
+                    .take_unix_listener(index)
+                    .map_err(|e| Error::Bind(name.clone(), e))?;
+
+                if let Some(listener) = listener {
+                    listener
+                        .set_nonblocking(true)
+                        .map_err(|e| Error::Bind(name.clone(), e))?;
+
+                    info!("Listening on {}", name);
+                    listeners.push(BoundListener::Unix(
+                        UnixListener::from_std(listener).map_err(|e| Error::Bind(name, e))?,
+                    ));
+                }
+            }
+
+            if listeners.is_empty() {
+                return Err(Error::NoSystemdSockets);
+            }
+
+            Ok(listeners)
+        }
+    }
+}
+
+/// Serves the application on all the listeners, until the shutdown is triggered. Once that
+/// happens, the listeners stop accepting connections and the in-flight requests get `drain_timeout`
+/// to finish.
+pub async fn serve(
+    application: Router,
+    listeners: Vec<Listener>,
+    shutdown: Shutdown,
+    drain_timeout: Duration,
+) -> Result<(), Error> {
+    // The sockets passed in by systemd are its to clean up
+    let socket_paths: Vec<PathBuf> = listeners
+        .iter()
+        .filter_map(|x| match x {
+            Listener::Unix(path) => Some(path.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let mut bound = vec![];
+    for listener in listeners {
+        bound.extend(bind(listener)?);
+    }
+
+    let mut servers = vec![];
+    for listener in bound {
+        let service = application
+            .clone()
+            .into_make_service_with_connect_info::<PeerAddress>();
+        let shutdown = shutdown.clone();
+        let signal = {
+            let shutdown = shutdown.clone();
+            async move { shutdown.wait().await }
+        };
+
+        let server = match listener {
+            BoundListener::Tcp(listener) => {
+                let server = axum::Server::from_tcp(listener)?
+                    .serve(service)
+                    .with_graceful_shutdown(signal);
+
+                tokio::spawn(async move {
+                    let result = server.await;
+                    // If one of the listeners fails, bring down the others as well
+                    shutdown.trigger();
+                    result
+                })
+            }
+            BoundListener::Unix(listener) => {
+                let server =
+                    axum::Server::builder(accept::from_stream(UnixListenerStream::new(listener)))
+                        .serve(service)
+                        .with_graceful_shutdown(signal);
+
+                tokio::spawn(async move {
+                    let result = server.await;
+                    shutdown.trigger();
+                    result
+                })
+            }
+        };
+
+        servers.push(server);
+    }
+
+    shutdown.wait().await;
+    info!("Shutting down, waiting for in-flight requests");
+
+    let deadline = Instant::now() + drain_timeout;
+    let mut result = Ok(());
+    for server in servers {
+        match tokio::time::timeout_at(deadline, server).await {
+            Ok(Ok(Ok(()))) => {}
+            Ok(Ok(Err(e))) => {
+                result = Err(e.into());
+                break;
+            }
+            Ok(Err(e)) => warn!("Server task failed: {}", e),
+            Err(_) => {
+                warn!("In-flight requests did not finish in {:?}", drain_timeout);
+                break;
+            }
+        }
+    }
+
+    // The sockets are this instance's, nobody else could bind them while it was listening
+    for path in socket_paths {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                warn!("Failed to remove the socket {}: {}", path.display(), e);
+            }
+            _ => {}
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::SocketAddr, path::PathBuf};
+
+    use super::*;
+
+    #[test]
+    pub fn can_parse_listeners() {
+        assert_eq!(
+            Listener::Tcp("0.0.0.0:8080".parse::<SocketAddr>().unwrap()),
+            "0.0.0.0:8080".parse().unwrap()
+        );
+        assert_eq!(
+            Listener::Tcp("[::1]:80".parse::<SocketAddr>().unwrap()),
+            "tcp:[::1]:80".parse().unwrap()
+        );
+        assert_eq!(
+            Listener::Unix(PathBuf::from("/run/backend.sock")),
+            "unix:/run/backend.sock".parse().unwrap()
+        );
+        assert_eq!(Listener::Systemd, " systemd ".parse().unwrap());
+    }
+
+    #[test]
+    pub fn removes_only_stale_sockets() {
+        let directory = std::env::temp_dir().join(format!("server-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let socket = directory.join("backend.sock");
+        let file = directory.join("backend.txt");
+
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        std::fs::write(&file, "").unwrap();
+
+        assert!(remove_stale_socket(&socket).is_err());
+        assert!(socket.exists());
+
+        drop(listener);
+        assert!(remove_stale_socket(&socket).is_ok());
+        assert!(!socket.exists());
+        assert!(remove_stale_socket(&socket).is_ok());
+        assert!(remove_stale_socket(&file).is_err());
+        assert!(file.exists());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    pub fn rejects_invalid_listeners() {
+        assert!("unix:".parse::<Listener>().is_err());
+        assert!("localhost".parse::<Listener>().is_err());
+        assert!("tcp:0.0.0.0".parse::<Listener>().is_err());
+    }
+}
@@ -0,0 +1,512 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    audit::AuditTarget,
+    error::{ApiError, Error},
+};
+
+use super::{
+    require_scope, Authenticated, Scope, ServiceAccount, ServiceAccountRepository,
+    ServiceAccountToken, ROOT_ACCOUNT_NAME,
+};
+
+/// Tokens that were not used for this long are reported as stale.
+const STALE_AFTER: Duration = Duration::days(90);
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    id: Uuid,
+    prefix: String,
+    scopes: Vec<Scope>,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    last_used_at: Option<OffsetDateTime>,
+    expired: bool,
+    stale: bool,
+}
+
+impl TokenResponse {
+    fn new(token: &ServiceAccountToken, now: OffsetDateTime) -> Self {
+        Self {
+            id: token.id,
+            prefix: token.prefix.clone(),
+            scopes: token.scopes.clone(),
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            expired: token.is_expired(now),
+            stale: token.last_used_at.unwrap_or(token.created_at) < now - STALE_AFTER,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AccountTokenResponse {
+    service_account_id: Uuid,
+    service_account_name: String,
+    #[serde(flatten)]
+    token: TokenResponse,
+}
+
+#[derive(Serialize)]
+pub struct AccountResponse {
+    id: Uuid,
+    name: String,
+    jwt_scopes: Vec<Scope>,
+    tokens: Vec<TokenResponse>,
+}
+
+impl AccountResponse {
+    fn new(account: &ServiceAccount, now: OffsetDateTime) -> Self {
+        Self {
+            id: account.id,
+            name: account.name.clone(),
+            jwt_scopes: account.jwt_scopes.clone(),
+            tokens: account
+                .tokens
+                .iter()
+                .map(|x| TokenResponse::new(x, now))
+                .collect(),
+        }
+    }
+}
+
+/// The only response that contains the token string, it can't be retrieved later.
+#[derive(Serialize)]
+pub struct IssuedTokenResponse {
+    token: TokenResponse,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+pub struct TokensQuery {
+    stale: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct AccountRequest {
+    name: String,
+    /// The scopes a JWT naming the account can be granted, none when the account is created, and
+    /// unchanged by a `PATCH` without them
+    jwt_scopes: Option<Vec<Scope>>,
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    scopes: Vec<Scope>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    expires_at: Option<OffsetDateTime>,
+}
+
+fn validate_name(name: &str) -> Result<String, Error> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        return Err(Error::Validation("The name cannot be empty".to_string()));
+    }
+
+    Ok(name.to_string())
+}
+
+async fn ensure_name_available(
+    repository: &ServiceAccountRepository,
+    name: &str,
+) -> Result<(), Error> {
+    if repository.find_by_name(name).await?.is_some() {
+        return Err(Error::Conflict(format!(
+            "A service account named {name} already exists"
+        )));
+    }
+
+    Ok(())
+}
+
+/// The root account is recreated by name on startup, so it can't be renamed or deleted.
+fn ensure_not_root(account: &ServiceAccount) -> Result<(), Error> {
+    if account.name == ROOT_ACCOUNT_NAME {
+        return Err(Error::Forbidden(
+            "The root account cannot be renamed or deleted".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The root account has to keep a token that can manage the accounts, otherwise the only way back
+/// in is `migrate reset-root-token`.
+fn ensure_root_keeps_a_token(account: &ServiceAccount, now: OffsetDateTime) -> Result<(), Error> {
+    let has_admin_token = account
+        .tokens
+        .iter()
+        .any(|x| !x.is_expired(now) && x.scopes.contains(&Scope::AccountsAdmin));
+
+    if account.name == ROOT_ACCOUNT_NAME && !has_admin_token {
+        return Err(Error::Forbidden(
+            "The last token of the root account cannot be revoked".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn find_account(
+    repository: &ServiceAccountRepository,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<ServiceAccount, Error> {
+    let Path(id) = id?;
+
+    repository.find_by_id(id).await?.ok_or(Error::NotFound)
+}
+
+pub async fn route_api_get_tokens(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+    Query(query): Query<TokensQuery>,
+) -> Result<Json<Vec<AccountTokenResponse>>, ApiError> {
+    let now = OffsetDateTime::now_utc();
+    let accounts = repository.find_all().await?;
+
+    let tokens = accounts
+        .iter()
+        .flat_map(|account| {
+            account
+                .tokens
+                .iter()
+                .map(move |token| AccountTokenResponse {
+                    service_account_id: account.id,
+                    service_account_name: account.name.clone(),
+                    token: TokenResponse::new(token, now),
+                })
+        })
+        .filter(|x| query.stale.is_none_or(|stale| x.token.stale == stale))
+        .collect();
+
+    Ok(Json(tokens))
+}
+
+pub async fn route_api_get_accounts(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+) -> Result<Json<Vec<AccountResponse>>, ApiError> {
+    let now = OffsetDateTime::now_utc();
+    let accounts = repository.find_all().await?;
+
+    Ok(Json(
+        accounts
+            .iter()
+            .map(|x| AccountResponse::new(x, now))
+            .collect(),
+    ))
+}
+
+pub async fn route_api_post_accounts(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+    authenticated: Authenticated,
+    request: Result<Json<AccountRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(request) = request?;
+    let name = validate_name(&request.name)?;
+    ensure_name_available(&repository, &name).await?;
+
+    let mut account = ServiceAccount::create(Uuid::new_v4(), name);
+    account.set_jwt_scopes(request.jwt_scopes.unwrap_or_default());
+    repository.save(&mut account).await?;
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        created_service_account.id = %account.id,
+        "Service account created"
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Extension(AuditTarget(format!("/api/service-accounts/{}", account.id))),
+        Json(AccountResponse::new(&account, OffsetDateTime::now_utc())),
+    ))
+}
+
+pub async fn route_api_get_account(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<AccountResponse>, ApiError> {
+    let account = find_account(&repository, id).await?;
+
+    Ok(Json(AccountResponse::new(
+        &account,
+        OffsetDateTime::now_utc(),
+    )))
+}
+
+pub async fn route_api_patch_account(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+    id: Result<Path<Uuid>, PathRejection>,
+    request: Result<Json<AccountRequest>, JsonRejection>,
+) -> Result<Json<AccountResponse>, ApiError> {
+    let Json(request) = request?;
+    let mut account = find_account(&repository, id).await?;
+    ensure_not_root(&account)?;
+
+    let name = validate_name(&request.name)?;
+    let rename = name != account.name;
+    let jwt_scopes = request.jwt_scopes.filter(|x| *x != account.jwt_scopes);
+
+    if rename {
+        ensure_name_available(&repository, &name).await?;
+        account.rename(name);
+    }
+    if let Some(scopes) = &jwt_scopes {
+        account.set_jwt_scopes(scopes.clone());
+    }
+    if rename || jwt_scopes.is_some() {
+        repository.save(&mut account).await?;
+    }
+
+    Ok(Json(AccountResponse::new(
+        &account,
+        OffsetDateTime::now_utc(),
+    )))
+}
+
+pub async fn route_api_delete_account(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+    authenticated: Authenticated,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<StatusCode, ApiError> {
+    let account = find_account(&repository, id).await?;
+    ensure_not_root(&account)?;
+
+    if !repository.delete(account.id).await? {
+        return Err(Error::NotFound.into());
+    }
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        deleted_service_account.id = %account.id,
+        "Service account deleted"
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn route_api_post_tokens(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+    authenticated: Authenticated,
+    id: Result<Path<Uuid>, PathRejection>,
+    request: Result<Json<TokenRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(request) = request?;
+    let mut account = find_account(&repository, id).await?;
+    let now = OffsetDateTime::now_utc();
+
+    if request.scopes.is_empty() {
+        return Err(Error::Validation("At least one scope is required".to_string()).into());
+    }
+
+    if request.expires_at.is_some_and(|x| x <= now) {
+        return Err(Error::Validation("The expiry must be in the future".to_string()).into());
+    }
+
+    let (token, secret) = ServiceAccountToken::create(
+        Uuid::new_v4(),
+        request.scopes,
+        request.expires_at,
+        thread_rng,
+    );
+    let response = TokenResponse::new(&token, now);
+    account.add_token(token);
+    repository.save(&mut account).await?;
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        issued_token.id = %response.id,
+        issued_token.service_account_id = %account.id,
+        "Token issued"
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Extension(AuditTarget(format!(
+            "/api/service-accounts/{}/tokens/{}",
+            account.id, response.id
+        ))),
+        Json(IssuedTokenResponse {
+            token: response,
+            secret,
+        }),
+    ))
+}
+
+pub async fn route_api_post_token_rotate(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+    authenticated: Authenticated,
+    ids: Result<Path<(Uuid, Uuid)>, PathRejection>,
+) -> Result<Json<IssuedTokenResponse>, ApiError> {
+    let Path((account_id, token_id)) = ids.map_err(Error::from)?;
+    let mut account = repository
+        .find_by_id(account_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let secret = account
+        .rotate_token(token_id, thread_rng)
+        .ok_or(Error::NotFound)?;
+    repository.save(&mut account).await?;
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        rotated_token.id = %token_id,
+        "Token rotated"
+    );
+
+    let token = account
+        .tokens
+        .iter()
+        .find(|x| x.id == token_id)
+        .ok_or(Error::NotFound)?;
+
+    Ok(Json(IssuedTokenResponse {
+        token: TokenResponse::new(token, OffsetDateTime::now_utc()),
+        secret,
+    }))
+}
+
+pub async fn route_api_delete_token(
+    State(repository): State<Arc<ServiceAccountRepository>>,
+    authenticated: Authenticated,
+    ids: Result<Path<(Uuid, Uuid)>, PathRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Path((account_id, token_id)) = ids.map_err(Error::from)?;
+    let mut account = repository
+        .find_by_id(account_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if !account.revoke_token(token_id) {
+        return Err(Error::NotFound.into());
+    }
+    ensure_root_keeps_a_token(&account, OffsetDateTime::now_utc())?;
+    repository.save(&mut account).await?;
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        revoked_token.id = %token_id,
+        "Token revoked"
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The management endpoints, all of them require the `accounts:admin` scope.
+pub fn router(repository: Arc<ServiceAccountRepository>) -> Router {
+    Router::new()
+        .route("/tokens", get(route_api_get_tokens))
+        .route(
+            "/service-accounts",
+            get(route_api_get_accounts).post(route_api_post_accounts),
+        )
+        .route(
+            "/service-accounts/:id",
+            get(route_api_get_account)
+                .patch(route_api_patch_account)
+                .delete(route_api_delete_account),
+        )
+        .route("/service-accounts/:id/tokens", post(route_api_post_tokens))
+        .route(
+            "/service-accounts/:id/tokens/:token_id",
+            axum::routing::delete(route_api_delete_token),
+        )
+        .route(
+            "/service-accounts/:id/tokens/:token_id/rotate",
+            post(route_api_post_token_rotate),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            Scope::AccountsAdmin,
+            require_scope,
+        ))
+        .with_state(repository)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn token(scopes: Vec<Scope>, expires_at: Option<OffsetDateTime>) -> ServiceAccountToken {
+        ServiceAccountToken::create(Uuid::new_v4(), scopes, expires_at, thread_rng).0
+    }
+
+    #[test]
+    pub fn validates_names() {
+        assert_eq!("ci", validate_name("  ci ").unwrap());
+        assert!(validate_name(" ").is_err());
+    }
+
+    #[test]
+    pub fn protects_the_root_account() {
+        let root = ServiceAccount::create(Uuid::new_v4(), ROOT_ACCOUNT_NAME.to_string());
+        let other = ServiceAccount::create(Uuid::new_v4(), "ci".to_string());
+
+        assert!(matches!(ensure_not_root(&root), Err(Error::Forbidden(_))));
+        assert!(ensure_not_root(&other).is_ok());
+    }
+
+    #[test]
+    pub fn root_account_keeps_an_admin_token() {
+        let now = OffsetDateTime::now_utc();
+        let mut root = ServiceAccount::create(Uuid::new_v4(), ROOT_ACCOUNT_NAME.to_string());
+        let mut other = ServiceAccount::create(Uuid::new_v4(), "ci".to_string());
+
+        assert!(matches!(
+            ensure_root_keeps_a_token(&root, now),
+            Err(Error::Forbidden(_))
+        ));
+        assert!(ensure_root_keeps_a_token(&other, now).is_ok());
+
+        root.add_token(token(vec![Scope::PostsWrite], None));
+        root.add_token(token(
+            vec![Scope::AccountsAdmin],
+            Some(now - Duration::days(1)),
+        ));
+        other.add_token(token(vec![Scope::PostsWrite], None));
+        assert!(ensure_root_keeps_a_token(&root, now).is_err());
+        assert!(ensure_root_keeps_a_token(&other, now).is_ok());
+
+        root.add_token(token(vec![Scope::AccountsAdmin], None));
+        assert!(ensure_root_keeps_a_token(&root, now).is_ok());
+    }
+
+    #[test]
+    pub fn reports_expired_and_stale_tokens() {
+        let now = OffsetDateTime::now_utc();
+
+        let fresh = token(vec![Scope::PostsRead], None);
+        let response = TokenResponse::new(&fresh, now);
+        assert!(!response.expired);
+        assert!(!response.stale);
+
+        let mut unused = token(vec![Scope::PostsRead], Some(now - Duration::days(1)));
+        unused.created_at = now - STALE_AFTER - Duration::days(1);
+        let response = TokenResponse::new(&unused, now);
+        assert!(response.expired);
+        assert!(response.stale);
+
+        unused.last_used_at = Some(now - Duration::days(1));
+        assert!(!TokenResponse::new(&unused, now).stale);
+    }
+}
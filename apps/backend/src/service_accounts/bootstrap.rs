@@ -0,0 +1,333 @@
+use std::{
+    fmt::{self, Debug, Display},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::{CryptoRng, Rng};
+use reqwest::{header::CONTENT_TYPE, StatusCode};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use tracing::info;
+use uuid::Uuid;
+
+use super::{token, Pepper, Scope};
+
+pub const ROOT_ACCOUNT_NAME: &str = "root";
+
+const KUBERNETES_SERVICE_ACCOUNT_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid root token output: {0}")]
+    InvalidOutput(String),
+    #[error("Failed to write the root token to {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Failed to write the root token to {0}: {1}")]
+    Kubernetes(String, String),
+    #[error("Database error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+}
+
+/// The full root token. It can't be formatted, so that it doesn't end up in the logs by accident,
+/// the only way out is through [`TokenOutput::write`].
+pub struct RootToken(String);
+
+impl Debug for RootToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RootToken(<redacted>)")
+    }
+}
+
+/// Where the root token goes when it's generated.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenOutput {
+    Stdout,
+    File(PathBuf),
+    /// A secret created through the Kubernetes API, the namespace defaults to the pod's own
+    KubernetesSecret {
+        namespace: Option<String>,
+        name: String,
+    },
+}
+
+impl FromStr for TokenOutput {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s == "stdout" {
+            return Ok(Self::Stdout);
+        }
+
+        if let Some(path) = s.strip_prefix("file:").filter(|x| !x.is_empty()) {
+            return Ok(Self::File(PathBuf::from(path)));
+        }
+
+        if let Some(secret) = s.strip_prefix("kubernetes:") {
+            let (namespace, name) = secret
+                .split_once('/')
+                .map_or((None, secret), |(namespace, name)| (Some(namespace), name));
+
+            if name.is_empty() || namespace.is_some_and(str::is_empty) {
+                return Err(Error::InvalidOutput(s.to_string()));
+            }
+
+            return Ok(Self::KubernetesSecret {
+                namespace: namespace.map(ToString::to_string),
+                name: name.to_string(),
+            });
+        }
+
+        Err(Error::InvalidOutput(s.to_string()))
+    }
+}
+
+impl Display for TokenOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "stdout"),
+            Self::File(path) => write!(f, "file:{}", path.display()),
+            Self::KubernetesSecret {
+                namespace: Some(namespace),
+                name,
+            } => write!(f, "kubernetes:{namespace}/{name}"),
+            Self::KubernetesSecret {
+                namespace: None,
+                name,
+            } => write!(f, "kubernetes:{name}"),
+        }
+    }
+}
+
+impl TokenOutput {
+    /// Reads `ROOT_TOKEN_OUTPUT`, which is one of `stdout` (the default), `file:<path>` or
+    /// `kubernetes:[<namespace>/]<secret name>`.
+    pub fn from_env() -> Result<Self, Error> {
+        std::env::var("ROOT_TOKEN_OUTPUT").map_or(Ok(Self::Stdout), |x| x.parse())
+    }
+
+    /// Unless `overwrite` is set, an existing file or secret is an error, so that a token that was
+    /// handed out already doesn't get silently replaced.
+    pub async fn write(&self, token: &RootToken, overwrite: bool) -> Result<(), Error> {
+        match self {
+            Self::Stdout => {
+                println!("Root service account token: {}", token.0);
+            }
+            Self::File(path) => write_file(path, token, overwrite)?,
+            Self::KubernetesSecret { namespace, name } => {
+                write_kubernetes_secret(namespace.as_deref(), name, token, overwrite)
+                    .await
+                    .map_err(|e| Error::Kubernetes(self.to_string(), e))?;
+            }
+        }
+
+        info!("The root token was written to {}", self);
+
+        Ok(())
+    }
+}
+
+fn write_file(path: &Path, token: &RootToken, overwrite: bool) -> Result<(), Error> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let error = |e| Error::Io(path.display().to_string(), e);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).mode(0o600);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+
+    let mut file = options.open(path).map_err(error)?;
+    writeln!(file, "{}", token.0).map_err(error)
+}
+
+async fn write_kubernetes_secret(
+    namespace: Option<&str>,
+    name: &str,
+    token: &RootToken,
+    overwrite: bool,
+) -> Result<(), String> {
+    let read = |file| {
+        std::fs::read_to_string(Path::new(KUBERNETES_SERVICE_ACCOUNT_PATH).join(file))
+            .map_err(|e| format!("Failed to read the service account {file}: {e}"))
+    };
+
+    let namespace = match namespace {
+        Some(namespace) => namespace.to_string(),
+        None => read("namespace")?.trim().to_string(),
+    };
+    let api_token = read("token")?;
+    let certificate =
+        reqwest::Certificate::from_pem(read("ca.crt")?.as_bytes()).map_err(|e| e.to_string())?;
+
+    let (Ok(host), Ok(port)) = (
+        std::env::var("KUBERNETES_SERVICE_HOST"),
+        std::env::var("KUBERNETES_SERVICE_PORT"),
+    ) else {
+        return Err("Not running in a Kubernetes cluster".to_string());
+    };
+    let host = if host.contains(':') {
+        format!("[{host}]")
+    } else {
+        host
+    };
+
+    let client = reqwest::Client::builder()
+        .add_root_certificate(certificate)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let url = format!("https://{host}:{port}/api/v1/namespaces/{namespace}/secrets");
+    let body = serde_json::json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": { "name": name },
+        "type": "Opaque",
+        "data": { "token": STANDARD.encode(&token.0) },
+    })
+    .to_string();
+
+    let send = |request: reqwest::RequestBuilder| {
+        request
+            .bearer_auth(api_token.trim())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.clone())
+            .send()
+    };
+
+    let mut response = send(client.post(&url)).await.map_err(|e| e.to_string())?;
+
+    if response.status() == StatusCode::CONFLICT && overwrite {
+        response = send(client.put(format!("{url}/{name}")))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "The API server responded with {}",
+            response.status()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Replaces all the tokens of the root account with a single new one, creating the account if it
+/// doesn't exist. This works on the database directly, so that it can be done from the `migrate`
+/// binary while the backend is running. Nothing changes unless the new token was written to the
+/// `output`.
+pub async fn reset_root_token<TCryptoRng: CryptoRng + Rng>(
+    db_pool: &Pool<Postgres>,
+    pepper: &Pepper,
+    output: &TokenOutput,
+    overwrite: bool,
+    csprng: impl (FnOnce() -> TCryptoRng) + Send,
+) -> Result<(), Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    let account_id = sqlx::query_scalar!(
+        "SELECT id FROM service_accounts WHERE name = $1",
+        ROOT_ACCOUNT_NAME
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    let account_id = if let Some(account_id) = account_id {
+        sqlx::query!(
+            "UPDATE service_accounts SET version = version + 1 WHERE id = $1",
+            account_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        account_id
+    } else {
+        let account_id = Uuid::new_v4();
+        sqlx::query!(
+            "INSERT INTO service_accounts (id, name) VALUES ($1, $2)",
+            account_id,
+            ROOT_ACCOUNT_NAME
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        account_id
+    };
+
+    sqlx::query!(
+        "DELETE FROM service_account_tokens WHERE service_account = $1",
+        account_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    let generated = token::generate(csprng);
+    let scopes: Vec<String> = Scope::ALL.iter().map(ToString::to_string).collect();
+    sqlx::query!(
+        "INSERT INTO service_account_tokens (id, prefix, secret_hash, signing_key, scopes, service_account) VALUES ($1, $2, $3, $4, $5, $6)",
+        Uuid::new_v4(),
+        generated.prefix,
+        generated.secret_hash,
+        pepper.seal(&generated.prefix, &generated.signing_key),
+        &scopes,
+        account_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    // Written before the commit, a token nobody has would only be recoverable by resetting it
+    output
+        .write(&RootToken(generated.content), overwrite)
+        .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    pub fn can_parse_outputs() {
+        assert_eq!(TokenOutput::Stdout, "stdout".parse().unwrap());
+        assert_eq!(
+            TokenOutput::File(PathBuf::from("/run/root-token")),
+            "file:/run/root-token".parse().unwrap()
+        );
+        assert_eq!(
+            TokenOutput::KubernetesSecret {
+                namespace: None,
+                name: "root-token".to_string()
+            },
+            "kubernetes:root-token".parse().unwrap()
+        );
+        assert_eq!(
+            TokenOutput::KubernetesSecret {
+                namespace: Some("default".to_string()),
+                name: "root-token".to_string()
+            },
+            "kubernetes:default/root-token".parse().unwrap()
+        );
+
+        assert!("file:".parse::<TokenOutput>().is_err());
+        assert!("kubernetes:/root-token".parse::<TokenOutput>().is_err());
+        assert!("stderr".parse::<TokenOutput>().is_err());
+    }
+
+    #[test]
+    pub fn root_tokens_are_not_formatted() {
+        let token = RootToken("prefix.secret".to_string());
+
+        assert!(!format!("{token:?}").contains("secret"));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+/// Failures that are not slowed down, so that a typo doesn't make anyone wait.
+const FREE_ATTEMPTS: u32 = 3;
+/// The delay after the first failure past the free ones, it doubles with every next one.
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_mins(1);
+const LOCKOUT_THRESHOLD: u32 = 20;
+const LOCKOUT_DURATION: Duration = Duration::from_mins(15);
+/// A client that didn't fail for this long starts from scratch.
+const FAILURE_WINDOW: Duration = Duration::from_mins(15);
+
+const NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(30);
+/// Past this, rejected credentials are not cached, so that the memory use is bounded.
+const NEGATIVE_CACHE_CAPACITY: usize = 10_000;
+
+const SWEEP_INTERVAL: Duration = Duration::from_mins(1);
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    last_failure_at: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl Failures {
+    fn blocked_until(&self) -> Option<Instant> {
+        if self.locked_until.is_some() {
+            return self.locked_until;
+        }
+
+        let delayed = self.count.checked_sub(FREE_ATTEMPTS)?;
+        let delay = BASE_DELAY
+            .saturating_mul(2_u32.saturating_pow(delayed))
+            .min(MAX_DELAY);
+
+        Some(self.last_failure_at + delay)
+    }
+
+    fn is_stale(&self, now: Instant) -> bool {
+        self.locked_until.map_or_else(
+            || now.saturating_duration_since(self.last_failure_at) > FAILURE_WINDOW,
+            |locked_until| now >= locked_until,
+        )
+    }
+}
+
+/// Tracks the failed authentication attempts of every client IP. Past a few failures the client
+/// has to wait exponentially longer before the next attempt, and after many it's locked out.
+/// Successful attempts don't reset the count, so that a client with one valid token can't use it to
+/// keep guessing others.
+///
+/// The clients without an IP, like the ones on a Unix socket without `X-Forwarded-For`, can't be
+/// told apart, one of them would lock out all the others. They are not tracked, only the
+/// [`NegativeCache`] applies to them.
+pub struct FailedAttempts {
+    state: Mutex<(HashMap<IpAddr, Failures>, Instant)>,
+}
+
+impl FailedAttempts {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new((HashMap::new(), Instant::now())),
+        }
+    }
+
+    /// Returns how long the client has to wait before it can try to authenticate again.
+    fn check_at(&self, client_ip: Option<IpAddr>, now: Instant) -> Result<(), Duration> {
+        let Some(client_ip) = client_ip else {
+            return Ok(());
+        };
+        let (clients, last_sweep) = &mut *self.state.lock().unwrap();
+
+        if now.saturating_duration_since(*last_sweep) > SWEEP_INTERVAL {
+            clients.retain(|_, failures| !failures.is_stale(now));
+            *last_sweep = now;
+        }
+
+        let Some(failures) = clients.get(&client_ip) else {
+            return Ok(());
+        };
+
+        if failures.is_stale(now) {
+            clients.remove(&client_ip);
+
+            return Ok(());
+        }
+
+        match failures.blocked_until() {
+            Some(blocked_until) if blocked_until > now => Err(blocked_until - now),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_failure_at(&self, client_ip: Option<IpAddr>, now: Instant) {
+        let Some(client_ip) = client_ip else {
+            return;
+        };
+        let (clients, _) = &mut *self.state.lock().unwrap();
+
+        let failures = clients.entry(client_ip).or_insert(Failures {
+            count: 0,
+            last_failure_at: now,
+            locked_until: None,
+        });
+
+        if failures.is_stale(now) {
+            *failures = Failures {
+                count: 0,
+                last_failure_at: now,
+                locked_until: None,
+            };
+        }
+
+        failures.count += 1;
+        failures.last_failure_at = now;
+
+        if failures.count >= LOCKOUT_THRESHOLD && failures.locked_until.is_none() {
+            failures.locked_until = Some(now + LOCKOUT_DURATION);
+
+            warn!(
+                client.ip = %client_ip,
+                failures = failures.count,
+                "Client {} locked out for {:?} after {} failed authentication attempts",
+                client_ip,
+                LOCKOUT_DURATION,
+                failures.count
+            );
+        }
+    }
+
+    pub fn check(&self, client_ip: Option<IpAddr>) -> Result<(), Duration> {
+        self.check_at(client_ip, Instant::now())
+    }
+
+    pub fn record_failure(&self, client_ip: Option<IpAddr>) {
+        self.record_failure_at(client_ip, Instant::now());
+    }
+}
+
+/// Remembers the credentials that were rejected for a short while, so that retrying them doesn't
+/// hit the database. Only their hashes are kept.
+pub struct NegativeCache {
+    state: Mutex<(HashMap<[u8; 32], Instant>, Instant)>,
+}
+
+impl NegativeCache {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new((HashMap::new(), Instant::now())),
+        }
+    }
+
+    fn key(credential: &str) -> [u8; 32] {
+        Sha256::digest(credential.as_bytes()).into()
+    }
+
+    fn contains_at(&self, credential: &str, now: Instant) -> bool {
+        let (entries, _) = &*self.state.lock().unwrap();
+
+        entries
+            .get(&Self::key(credential))
+            .is_some_and(|expires_at| *expires_at > now)
+    }
+
+    fn insert_at(&self, credential: &str, now: Instant) {
+        let (entries, last_sweep) = &mut *self.state.lock().unwrap();
+
+        if now.saturating_duration_since(*last_sweep) > SWEEP_INTERVAL {
+            entries.retain(|_, expires_at| *expires_at > now);
+            *last_sweep = now;
+        }
+
+        if entries.len() < NEGATIVE_CACHE_CAPACITY {
+            entries.insert(Self::key(credential), now + NEGATIVE_CACHE_TTL);
+        }
+    }
+
+    pub fn contains(&self, credential: &str) -> bool {
+        self.contains_at(credential, Instant::now())
+    }
+
+    pub fn insert(&self, credential: &str) {
+        self.insert_at(credential, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const CLIENT_IP: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    #[test]
+    pub fn backs_off_exponentially() {
+        let attempts = FailedAttempts::new();
+        let now = Instant::now();
+
+        for _ in 0..FREE_ATTEMPTS - 1 {
+            attempts.record_failure_at(CLIENT_IP, now);
+        }
+        assert!(attempts.check_at(CLIENT_IP, now).is_ok());
+
+        attempts.record_failure_at(CLIENT_IP, now);
+        assert_eq!(Err(BASE_DELAY), attempts.check_at(CLIENT_IP, now));
+        assert!(attempts.check_at(CLIENT_IP, now + BASE_DELAY).is_ok());
+
+        attempts.record_failure_at(CLIENT_IP, now + BASE_DELAY);
+        assert_eq!(
+            Err(BASE_DELAY * 2),
+            attempts.check_at(CLIENT_IP, now + BASE_DELAY)
+        );
+
+        assert!(attempts.check_at(None, now).is_ok());
+    }
+
+    #[test]
+    pub fn locks_out_after_repeated_failures() {
+        let attempts = FailedAttempts::new();
+        let now = Instant::now();
+
+        for _ in 0..LOCKOUT_THRESHOLD {
+            attempts.record_failure_at(CLIENT_IP, now);
+        }
+
+        assert_eq!(Err(LOCKOUT_DURATION), attempts.check_at(CLIENT_IP, now));
+        assert!(attempts.check_at(CLIENT_IP, now + LOCKOUT_DURATION).is_ok());
+
+        // The failures are forgotten with the lockout
+        attempts.record_failure_at(CLIENT_IP, now + LOCKOUT_DURATION);
+        assert!(attempts.check_at(CLIENT_IP, now + LOCKOUT_DURATION).is_ok());
+    }
+
+    #[test]
+    pub fn doesnt_lock_out_clients_without_an_ip() {
+        let attempts = FailedAttempts::new();
+        let now = Instant::now();
+
+        for _ in 0..LOCKOUT_THRESHOLD {
+            attempts.record_failure_at(None, now);
+        }
+
+        assert!(attempts.check_at(None, now).is_ok());
+        assert!(attempts.check_at(CLIENT_IP, now).is_ok());
+    }
+
+    #[test]
+    pub fn caches_rejected_credentials_briefly() {
+        let cache = NegativeCache::new();
+        let now = Instant::now();
+
+        cache.insert_at("prefix.secret", now);
+
+        assert!(cache.contains_at("prefix.secret", now));
+        assert!(!cache.contains_at("prefix.other", now));
+        assert!(!cache.contains_at("prefix.secret", now + NEGATIVE_CACHE_TTL));
+    }
+}
@@ -0,0 +1,864 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, OriginalUri, State},
+    http::{
+        header::AUTHORIZATION, request::Parts, uri::PathAndQuery, HeaderMap, HeaderValue, Method,
+        Request,
+    },
+    middleware::Next,
+};
+use http_body::Limited;
+use rand::{CryptoRng, Rng};
+use sqlx::{Pool, Postgres};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    client_ip::ClientIp,
+    error::{ApiError, Error},
+};
+
+pub use self::{
+    bootstrap::TokenOutput,
+    scope::Scope,
+    signature::{NonceStore, Pepper},
+    usage::TokenUsage,
+};
+use self::{
+    bootstrap::ROOT_ACCOUNT_NAME,
+    brute_force::{FailedAttempts, NegativeCache},
+    jwt::JwtValidator,
+    signature::{SignatureHeader, SIGNATURE_HEADER},
+    token::SigningKey,
+};
+
+pub mod api;
+pub mod bootstrap;
+mod brute_force;
+pub mod jwt;
+mod scope;
+mod signature;
+mod token;
+mod usage;
+
+/// The signed requests are buffered whole, to compute the digest of the body.
+const MAX_SIGNED_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Only the hash of the secret part is stored, the full token is known only to its user. The key
+/// for signed requests is stored masked with the [`Pepper`].
+#[derive(Debug, Clone)]
+pub struct ServiceAccountToken {
+    id: Uuid,
+    prefix: String,
+    secret_hash: Vec<u8>,
+    /// `None` for the tokens created before the keys were stored, they have to be rotated to sign
+    /// requests
+    signing_key: Option<SigningKey>,
+    scopes: Vec<Scope>,
+    created_at: OffsetDateTime,
+    expires_at: Option<OffsetDateTime>,
+    last_used_at: Option<OffsetDateTime>,
+}
+
+impl ServiceAccountToken {
+    /// Returns the token, and the string that has to be presented to authenticate with it. The
+    /// string can't be recovered later.
+    pub fn create<TCryptoRng: CryptoRng + Rng>(
+        id: Uuid,
+        scopes: Vec<Scope>,
+        expires_at: Option<OffsetDateTime>,
+        csprng: impl FnOnce() -> TCryptoRng,
+    ) -> (Self, String) {
+        let generated = token::generate(csprng);
+
+        (
+            Self {
+                id,
+                prefix: generated.prefix,
+                secret_hash: generated.secret_hash,
+                signing_key: Some(generated.signing_key),
+                scopes,
+                created_at: OffsetDateTime::now_utc(),
+                expires_at,
+                last_used_at: None,
+            },
+            generated.content,
+        )
+    }
+
+    /// Replaces the secret, keeping the scopes and the expiry. The old string stops working once
+    /// the token is saved.
+    fn rotate<TCryptoRng: CryptoRng + Rng>(
+        &mut self,
+        csprng: impl FnOnce() -> TCryptoRng,
+    ) -> String {
+        let (token, content) = Self::create(self.id, self.scopes.clone(), self.expires_at, csprng);
+
+        self.prefix = token.prefix;
+        self.secret_hash = token.secret_hash;
+        self.signing_key = token.signing_key;
+
+        content
+    }
+
+    fn verify(&self, secret: &str) -> bool {
+        self.secret_hash.ct_eq(&token::hash_secret(secret)).into()
+    }
+
+    fn is_expired(&self, now: OffsetDateTime) -> bool {
+        self.expires_at.is_some_and(|x| x <= now)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceAccount {
+    id: Uuid,
+    name: String,
+    tokens: Vec<ServiceAccountToken>,
+    /// The scopes that a JWT naming the account can be granted
+    jwt_scopes: Vec<Scope>,
+    /// The version that was loaded from the database, 0 for accounts that were never saved
+    version: i64,
+}
+
+impl ServiceAccount {
+    pub const fn create(id: Uuid, name: String) -> Self {
+        Self {
+            id,
+            name,
+            tokens: vec![],
+            jwt_scopes: vec![],
+            version: 0,
+        }
+    }
+
+    pub const fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn rename(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn set_jwt_scopes(&mut self, scopes: Vec<Scope>) {
+        self.jwt_scopes = scopes;
+    }
+
+    /// The scopes claimed by a JWT that the account allows.
+    fn granted_jwt_scopes(&self, claimed: Vec<Scope>) -> Vec<Scope> {
+        claimed
+            .into_iter()
+            .filter(|x| self.jwt_scopes.contains(x))
+            .collect()
+    }
+
+    pub fn add_token(&mut self, token: ServiceAccountToken) {
+        self.tokens.push(token);
+    }
+
+    /// Returns the new string for the token, or `None` if the account has no such token.
+    pub fn rotate_token<TCryptoRng: CryptoRng + Rng>(
+        &mut self,
+        token_id: Uuid,
+        csprng: impl FnOnce() -> TCryptoRng,
+    ) -> Option<String> {
+        self.tokens
+            .iter_mut()
+            .find(|x| x.id == token_id)
+            .map(|x| x.rotate(csprng))
+    }
+
+    /// Returns `false` if the account has no such token.
+    pub fn revoke_token(&mut self, token_id: Uuid) -> bool {
+        let count = self.tokens.len();
+        self.tokens.retain(|x| x.id != token_id);
+
+        self.tokens.len() != count
+    }
+}
+
+/// The service account that made the request, along with the scopes of the token it used.
+#[derive(Debug, Clone)]
+pub struct Authenticated {
+    account: ServiceAccount,
+    /// `None` when the request was authenticated with a JWT
+    token_id: Option<Uuid>,
+    scopes: Vec<Scope>,
+}
+
+impl Authenticated {
+    pub const fn account(&self) -> &ServiceAccount {
+        &self.account
+    }
+
+    pub const fn token_id(&self) -> Option<Uuid> {
+        self.token_id
+    }
+
+    pub fn require(&self, scope: Scope) -> Result<(), Error> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(Error::Forbidden(format!(
+                "The token is missing the {scope} scope"
+            )))
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Authenticated {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or_else(|| Error::Unauthorized.into())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RepositoryError {
+    #[error("Database Error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("The service account {0} was changed by someone else in the meantime")]
+    ConcurrentModification(Uuid),
+    #[error("A service account named {0} already exists")]
+    DuplicateName(String),
+}
+
+impl RepositoryError {
+    fn from_sqlx(error: sqlx::Error, name: &str) -> Self {
+        match &error {
+            sqlx::Error::Database(e) if e.constraint() == Some("service_accounts_name_key") => {
+                Self::DuplicateName(name.to_string())
+            }
+            _ => Self::Sqlx(error),
+        }
+    }
+}
+
+/// Scopes that are no longer known are not granted.
+fn parse_scopes(scopes: &[String]) -> Vec<Scope> {
+    scopes.iter().filter_map(|x| x.parse().ok()).collect()
+}
+
+pub struct ServiceAccountRepository {
+    db_pool: Arc<Pool<Postgres>>,
+    pepper: Arc<Pepper>,
+}
+
+impl ServiceAccountRepository {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>, pepper: Arc<Pepper>) -> Self {
+        Self { db_pool, pepper }
+    }
+
+    pub async fn find_by_name(
+        &self,
+        name: impl Into<&str> + Send,
+    ) -> Result<Option<ServiceAccount>, sqlx::Error> {
+        let name: &str = name.into();
+
+        let account = sqlx::query!(
+            "SELECT id, name, jwt_scopes, version FROM service_accounts WHERE name=$1",
+            name
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await?;
+
+        let Some(account) = account else {
+            return Ok(None);
+        };
+
+        let tokens = self.find_tokens_for_account(account.id).await?;
+
+        Ok(Some(ServiceAccount {
+            id: account.id,
+            name: account.name,
+            tokens,
+            jwt_scopes: parse_scopes(&account.jwt_scopes),
+            version: account.version,
+        }))
+    }
+
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Option<ServiceAccount>, sqlx::Error> {
+        let account = sqlx::query!(
+            "SELECT id, name, jwt_scopes, version FROM service_accounts WHERE id=$1",
+            id
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await?;
+
+        let Some(account) = account else {
+            return Ok(None);
+        };
+
+        let tokens = self.find_tokens_for_account(account.id).await?;
+
+        Ok(Some(ServiceAccount {
+            id: account.id,
+            name: account.name,
+            tokens,
+            jwt_scopes: parse_scopes(&account.jwt_scopes),
+            version: account.version,
+        }))
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<ServiceAccount>, sqlx::Error> {
+        let accounts = sqlx::query!(
+            "SELECT id, name, jwt_scopes, version FROM service_accounts ORDER BY name"
+        )
+        .fetch_all(self.db_pool.as_ref())
+        .await?;
+
+        let mut result = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            result.push(ServiceAccount {
+                id: account.id,
+                name: account.name,
+                tokens: self.find_tokens_for_account(account.id).await?,
+                jwt_scopes: parse_scopes(&account.jwt_scopes),
+                version: account.version,
+            });
+        }
+
+        Ok(result)
+    }
+
+    pub async fn find_by_token(
+        &self,
+        token: impl Into<&str> + Send,
+    ) -> Result<Option<Authenticated>, sqlx::Error> {
+        let Some((prefix, secret)) = token::split(token.into()) else {
+            return Ok(None);
+        };
+
+        self.find_by_prefix(prefix, |x| x.verify(secret)).await
+    }
+
+    /// Finds the account by the prefix of one of its tokens, if that token is not expired and
+    /// passes the `verify` check.
+    pub async fn find_by_prefix(
+        &self,
+        prefix: &str,
+        verify: impl FnOnce(&ServiceAccountToken) -> bool + Send,
+    ) -> Result<Option<Authenticated>, sqlx::Error> {
+        let account = sqlx::query!("SELECT id, name, jwt_scopes, version FROM service_accounts WHERE id = (SELECT service_account FROM service_account_tokens WHERE prefix=$1)", prefix)
+            .fetch_optional(self.db_pool.as_ref())
+            .await?;
+
+        let Some(account) = account else {
+            return Ok(None);
+        };
+
+        let tokens = self.find_tokens_for_account(account.id).await?;
+
+        let now = OffsetDateTime::now_utc();
+        let Some(token) = tokens
+            .iter()
+            .find(|x| x.prefix == prefix && !x.is_expired(now))
+            .filter(|x| verify(x))
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(Authenticated {
+            token_id: Some(token.id),
+            scopes: token.scopes.clone(),
+            account: ServiceAccount {
+                id: account.id,
+                name: account.name,
+                tokens,
+                jwt_scopes: parse_scopes(&account.jwt_scopes),
+                version: account.version,
+            },
+        }))
+    }
+
+    /// Saves the account and its tokens in one transaction. Tokens that were removed from the
+    /// account are deleted, and the ones that didn't change are left alone. If the account was
+    /// changed since it was loaded, nothing is saved.
+    pub async fn save(&self, account: &mut ServiceAccount) -> Result<(), RepositoryError> {
+        let mut transaction = self.db_pool.begin().await?;
+
+        let jwt_scopes: Vec<String> = account
+            .jwt_scopes
+            .iter()
+            .map(|x| x.as_str().to_string())
+            .collect();
+        let result = if account.version == 0 {
+            sqlx::query!(
+                "INSERT INTO service_accounts (id, name, jwt_scopes, version) VALUES ($1, $2, $3, 1) ON CONFLICT (id) DO NOTHING",
+                account.id,
+                account.name,
+                &jwt_scopes
+            )
+            .execute(&mut *transaction)
+            .await
+        } else {
+            sqlx::query!(
+                "UPDATE service_accounts SET name = $2, jwt_scopes = $3, version = version + 1 WHERE id = $1 AND version = $4",
+                account.id,
+                account.name,
+                &jwt_scopes,
+                account.version
+            )
+            .execute(&mut *transaction)
+            .await
+        }
+        .map_err(|e| RepositoryError::from_sqlx(e, &account.name))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::ConcurrentModification(account.id));
+        }
+
+        let token_ids: Vec<Uuid> = account.tokens.iter().map(|x| x.id).collect();
+        sqlx::query!(
+            "DELETE FROM service_account_tokens WHERE service_account = $1 AND NOT (id = ANY($2))",
+            account.id,
+            &token_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        // Postgres can't unnest an array of arrays into rows, so the scopes of each token are joined
+        let scopes: Vec<String> = account
+            .tokens
+            .iter()
+            .map(|x| {
+                x.scopes
+                    .iter()
+                    .map(|x| x.as_str())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect();
+        let prefixes: Vec<String> = account.tokens.iter().map(|x| x.prefix.clone()).collect();
+        let secret_hashes: Vec<Vec<u8>> = account
+            .tokens
+            .iter()
+            .map(|x| x.secret_hash.clone())
+            .collect();
+        let signing_keys: Vec<Option<Vec<u8>>> = account
+            .tokens
+            .iter()
+            .map(|x| {
+                x.signing_key
+                    .as_ref()
+                    .map(|key| self.pepper.seal(&x.prefix, key))
+            })
+            .collect();
+        let created_at: Vec<OffsetDateTime> = account.tokens.iter().map(|x| x.created_at).collect();
+        let expires_at: Vec<Option<OffsetDateTime>> =
+            account.tokens.iter().map(|x| x.expires_at).collect();
+
+        sqlx::query!(
+            "INSERT INTO service_account_tokens AS t (id, prefix, secret_hash, signing_key, scopes, created_at, expires_at, service_account)
+                SELECT id, prefix, secret_hash, signing_key, COALESCE(string_to_array(NULLIF(scopes, ''), ','), '{}'), created_at, expires_at, $8
+                    FROM UNNEST($1::uuid[], $2::text[], $3::bytea[], $4::bytea[], $5::text[], $6::timestamptz[], $7::timestamptz[])
+                        AS u(id, prefix, secret_hash, signing_key, scopes, created_at, expires_at)
+                ON CONFLICT (id) DO UPDATE SET
+                    prefix = EXCLUDED.prefix,
+                    secret_hash = EXCLUDED.secret_hash,
+                    signing_key = EXCLUDED.signing_key,
+                    scopes = EXCLUDED.scopes,
+                    expires_at = EXCLUDED.expires_at
+                WHERE t.service_account = EXCLUDED.service_account
+                    AND (t.prefix, t.secret_hash, t.signing_key, t.scopes, t.expires_at)
+                        IS DISTINCT FROM (EXCLUDED.prefix, EXCLUDED.secret_hash, EXCLUDED.signing_key, EXCLUDED.scopes, EXCLUDED.expires_at)",
+            &token_ids,
+            &prefixes,
+            &secret_hashes,
+            &signing_keys as &[Option<Vec<u8>>],
+            &scopes,
+            &created_at,
+            &expires_at as &[Option<OffsetDateTime>],
+            account.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        account.version += 1;
+
+        Ok(())
+    }
+
+    /// Returns `false` if there was no such account. The tokens are deleted along with it.
+    pub async fn delete(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM service_accounts WHERE id = $1", id)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn find_tokens_for_account(
+        &self,
+        account_id: Uuid,
+    ) -> Result<Vec<ServiceAccountToken>, sqlx::Error> {
+        let tokens = sqlx::query!(
+            "SELECT id, prefix, secret_hash, signing_key, scopes, created_at, expires_at, last_used_at FROM service_account_tokens WHERE service_account=$1 ORDER BY created_at",
+            account_id
+        )
+        .fetch_all(self.db_pool.as_ref())
+        .await?;
+
+        Ok(tokens
+            .into_iter()
+            .map(|x| ServiceAccountToken {
+                id: x.id,
+                signing_key: x
+                    .signing_key
+                    .map(|sealed| self.pepper.open(&x.prefix, &sealed)),
+                prefix: x.prefix,
+                secret_hash: x.secret_hash,
+                scopes: parse_scopes(&x.scopes),
+                created_at: x.created_at,
+                expires_at: x.expires_at,
+                last_used_at: x.last_used_at,
+            })
+            .collect())
+    }
+}
+
+/// Creates the root account on the first start, and writes its token to the `output`. If the
+/// token gets lost, it has to be reset with `migrate reset-root-token`.
+pub async fn initialize_root_account<TCryptoRng: CryptoRng + Rng>(
+    repository: Arc<ServiceAccountRepository>,
+    output: &TokenOutput,
+    csprng: impl (FnOnce() -> TCryptoRng) + Send,
+) -> Result<(), bootstrap::Error> {
+    let current_account = repository.find_by_name(ROOT_ACCOUNT_NAME).await?;
+
+    if current_account.is_none() {
+        bootstrap::reset_root_token(
+            &repository.db_pool,
+            &repository.pepper,
+            output,
+            false,
+            csprng,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Only the media uploads can be larger than [`MAX_SIGNED_BODY_SIZE`], `path` is the one under
+/// `/api`.
+fn max_signed_body_size(method: &Method, path: &str) -> usize {
+    if *method == Method::PUT && path.starts_with("/media/") {
+        crate::blog::media::MAX_SIZE
+    } else {
+        MAX_SIGNED_BODY_SIZE
+    }
+}
+
+/// The state of the authentication middleware.
+pub struct Authenticator {
+    repository: Arc<ServiceAccountRepository>,
+    token_usage: Arc<TokenUsage>,
+    nonces: Arc<NonceStore>,
+    jwt: Option<Arc<JwtValidator>>,
+    failed_attempts: FailedAttempts,
+    rejected_tokens: NegativeCache,
+}
+
+impl Authenticator {
+    pub fn new(
+        repository: Arc<ServiceAccountRepository>,
+        token_usage: Arc<TokenUsage>,
+        nonces: Arc<NonceStore>,
+        jwt: Option<Arc<JwtValidator>>,
+    ) -> Self {
+        Self {
+            repository,
+            token_usage,
+            nonces,
+            jwt,
+            failed_attempts: FailedAttempts::new(),
+            rejected_tokens: NegativeCache::new(),
+        }
+    }
+
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Authenticated, Error> {
+        if let Some(authorization) = headers.get(AUTHORIZATION) {
+            match self.authenticate_jwt(authorization).await {
+                // The header may be meant for something else, like a proxy in front of the blog
+                Err(Error::Unauthorized) if headers.contains_key("X-Token") => {}
+                result => return result,
+            }
+        }
+
+        let token = headers
+            .get("X-Token")
+            .ok_or(Error::Unauthorized)?
+            .to_str()
+            .map_err(|_| Error::Unauthorized)?;
+
+        if self.rejected_tokens.contains(token) {
+            return Err(Error::Unauthorized);
+        }
+
+        let Some(authenticated) = self.repository.find_by_token(token).await? else {
+            self.rejected_tokens.insert(token);
+
+            return Err(Error::Unauthorized);
+        };
+
+        if let Some(token_id) = authenticated.token_id {
+            self.token_usage.record(token_id);
+        }
+
+        Ok(authenticated)
+    }
+
+    /// The JWT has to name an existing service account, the scopes come from the claims. Only the
+    /// ones the account allows for JWTs are granted, the identity provider can't grant more.
+    async fn authenticate_jwt(&self, authorization: &HeaderValue) -> Result<Authenticated, Error> {
+        let token = authorization
+            .to_str()
+            .ok()
+            .and_then(|x| x.strip_prefix("Bearer "))
+            .ok_or(Error::Unauthorized)?;
+        let claims = self
+            .jwt
+            .as_ref()
+            .and_then(|x| x.validate(token.trim()))
+            .ok_or(Error::Unauthorized)?;
+
+        let account = self
+            .repository
+            .find_by_name(claims.account_name.as_str())
+            .await?
+            .ok_or(Error::Unauthorized)?;
+
+        let scopes = account.granted_jwt_scopes(claims.scopes);
+
+        Ok(Authenticated {
+            account,
+            token_id: None,
+            scopes,
+        })
+    }
+
+    async fn authenticate_signed(
+        &self,
+        header: &str,
+        parts: &Parts,
+        body: &[u8],
+    ) -> Result<Authenticated, Error> {
+        let header = SignatureHeader::parse(header).ok_or(Error::Unauthorized)?;
+
+        if !header.is_fresh(OffsetDateTime::now_utc()) {
+            return Err(Error::Unauthorized);
+        }
+
+        // Nesting strips the prefix from the URI, the client signs the full one
+        let uri = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map_or(&parts.uri, |x| &x.0);
+        let canonical_request = signature::canonical_request(
+            parts.method.as_str(),
+            uri.path_and_query().map_or("/", PathAndQuery::as_str),
+            header.timestamp,
+            &header.nonce,
+            body,
+        );
+
+        let mut has_signing_key = true;
+        let authenticated = self
+            .repository
+            .find_by_prefix(&header.key_id, |token| {
+                has_signing_key = token.signing_key.is_some();
+
+                token.signing_key.as_ref().is_some_and(|key| {
+                    signature::verify(&key.0, &canonical_request, &header.signature)
+                })
+            })
+            .await?
+            // Not a guess, the token exists, it only has to be rotated
+            .ok_or(if has_signing_key {
+                Error::Unauthorized
+            } else {
+                Error::NoSigningKey
+            })?;
+
+        // Only the nonces of valid signatures are stored, so that anyone can't fill up the store
+        if !self
+            .nonces
+            .remember(&header.key_id, &header.nonce, header.timestamp)
+            .await?
+        {
+            return Err(Error::Unauthorized);
+        }
+
+        if let Some(token_id) = authenticated.token_id {
+            self.token_usage.record(token_id);
+        }
+
+        Ok(authenticated)
+    }
+
+    async fn authenticate_request(
+        &self,
+        request: Request<Body>,
+    ) -> Result<(Authenticated, Request<Body>), Error> {
+        let signature = request
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .map(|x| x.to_str().map(ToString::to_string))
+            .transpose()
+            .map_err(|_| Error::Unauthorized)?;
+
+        let Some(signature) = signature else {
+            return Ok((self.authenticate(request.headers()).await?, request));
+        };
+
+        let (parts, body) = request.into_parts();
+        let limit = max_signed_body_size(&parts.method, parts.uri.path());
+        let body = hyper::body::to_bytes(Limited::new(body, limit))
+            .await
+            .map_err(|_| Error::Validation("The request body is too large".to_string()))?;
+
+        let authenticated = self.authenticate_signed(&signature, &parts, &body).await?;
+
+        Ok((authenticated, Request::from_parts(parts, Body::from(body))))
+    }
+}
+
+/// Authenticates the request with the token in `X-Token`, with a signature in `X-Signature` made
+/// with the key derived from the token's secret, or with a JWT in `Authorization`. Clients that
+/// keep failing to authenticate are slowed down, and then locked out for a while.
+pub async fn middleware(
+    State(authenticator): State<Arc<Authenticator>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Result<axum::response::Response, ApiError> {
+    let client_ip = request.extensions().get::<ClientIp>().and_then(|x| x.0);
+    let has_credentials = [SIGNATURE_HEADER, "X-Token", AUTHORIZATION.as_str()]
+        .into_iter()
+        .any(|x| request.headers().contains_key(x));
+
+    authenticator
+        .failed_attempts
+        .check(client_ip)
+        .map_err(|retry_after| Error::TooManyRequests { retry_after })?;
+
+    let (authenticated, mut request) = match authenticator.authenticate_request(request).await {
+        // A request without any credentials is not a guess
+        Err(Error::Unauthorized) if has_credentials => {
+            authenticator.failed_attempts.record_failure(client_ip);
+
+            return Err(Error::Unauthorized.into());
+        }
+        result => result?,
+    };
+
+    request.extensions_mut().insert(authenticated);
+
+    Ok(next.run(request).await)
+}
+
+/// Rejects the requests made with tokens that don't have the scope, use with
+/// `route_layer(from_fn_with_state(scope, require_scope))`.
+pub async fn require_scope<B: Send>(
+    State(scope): State<Scope>,
+    authenticated: Authenticated,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<axum::response::Response, ApiError> {
+    authenticated.require(scope)?;
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod test {
+    use rand::thread_rng;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    pub fn can_verify_created_tokens() {
+        let (token, content) =
+            ServiceAccountToken::create(Uuid::new_v4(), vec![Scope::PostsRead], None, thread_rng);
+        let (prefix, secret) = token::split(&content).unwrap();
+
+        assert_eq!(token.prefix, prefix);
+        assert!(token.verify(secret));
+        assert!(!token.verify("wrong"));
+    }
+
+    #[test]
+    pub fn can_rotate_and_revoke_tokens() {
+        let mut account = ServiceAccount::create(Uuid::new_v4(), "test".to_string());
+        let (token, old_content) =
+            ServiceAccountToken::create(Uuid::new_v4(), vec![Scope::PostsRead], None, thread_rng);
+        let token_id = token.id;
+        account.add_token(token);
+
+        let new_content = account.rotate_token(token_id, thread_rng).unwrap();
+        let (_, old_secret) = token::split(&old_content).unwrap();
+        let (new_prefix, new_secret) = token::split(&new_content).unwrap();
+
+        assert_eq!(new_prefix, account.tokens[0].prefix);
+        assert!(account.tokens[0].verify(new_secret));
+        assert!(!account.tokens[0].verify(old_secret));
+        assert_eq!(vec![Scope::PostsRead], account.tokens[0].scopes);
+
+        assert!(account.rotate_token(Uuid::new_v4(), thread_rng).is_none());
+        assert!(!account.revoke_token(Uuid::new_v4()));
+        assert!(account.revoke_token(token_id));
+        assert!(account.tokens.is_empty());
+    }
+
+    #[test]
+    pub fn jwts_get_only_the_allowed_scopes() {
+        let mut account = ServiceAccount::create(Uuid::new_v4(), "test".to_string());
+        assert!(account
+            .granted_jwt_scopes(vec![Scope::PostsRead])
+            .is_empty());
+
+        account.set_jwt_scopes(vec![Scope::PostsRead, Scope::PostsWrite]);
+        assert_eq!(
+            vec![Scope::PostsWrite],
+            account.granted_jwt_scopes(vec![Scope::PostsWrite, Scope::AccountsAdmin])
+        );
+    }
+
+    #[test]
+    pub fn only_media_uploads_can_be_large() {
+        assert_eq!(
+            crate::blog::media::MAX_SIZE,
+            max_signed_body_size(&Method::PUT, "/media/abc")
+        );
+        assert_eq!(
+            MAX_SIGNED_BODY_SIZE,
+            max_signed_body_size(&Method::PUT, "/posts/abc")
+        );
+        assert_eq!(
+            MAX_SIGNED_BODY_SIZE,
+            max_signed_body_size(&Method::POST, "/media/abc")
+        );
+    }
+
+    #[test]
+    pub fn tokens_expire() {
+        let now = OffsetDateTime::now_utc();
+        let (token, _) = ServiceAccountToken::create(Uuid::new_v4(), vec![], Some(now), thread_rng);
+
+        assert!(!token.is_expired(now - time::Duration::seconds(1)));
+        assert!(token.is_expired(now));
+    }
+}
@@ -0,0 +1,81 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Unknown scope: {0}")]
+pub struct UnknownScopeError(String);
+
+/// A permission granted to a service account token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Scope {
+    PostsRead,
+    PostsWrite,
+    PostsDelete,
+    AccountsAdmin,
+}
+
+impl Scope {
+    pub const ALL: [Self; 4] = [
+        Self::PostsRead,
+        Self::PostsWrite,
+        Self::PostsDelete,
+        Self::AccountsAdmin,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::PostsRead => "posts:read",
+            Self::PostsWrite => "posts:write",
+            Self::PostsDelete => "posts:delete",
+            Self::AccountsAdmin => "accounts:admin",
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = UnknownScopeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.as_str() == s)
+            .ok_or_else(|| UnknownScopeError(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = UnknownScopeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Scope> for String {
+    fn from(value: Scope) -> Self {
+        value.as_str().to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn can_round_trip_scopes() {
+        for scope in Scope::ALL {
+            assert_eq!(scope, scope.to_string().parse().unwrap());
+        }
+
+        assert!("posts:publish".parse::<Scope>().is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::{sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres};
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::secrets;
+
+use super::token::SigningKey;
+
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+/// How far the timestamp of a signed request can be from the server's clock.
+pub const MAX_CLOCK_SKEW: time::Duration = time::Duration::minutes(5);
+
+const NONCE_CLEANUP_INTERVAL: Duration = Duration::from_mins(1);
+
+/// The `X-Signature` header, in the `key_id=<token prefix>, timestamp=<unix seconds>,
+/// nonce=<random>, signature=<base64>` format.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SignatureHeader {
+    pub key_id: String,
+    pub timestamp: i64,
+    pub nonce: String,
+    pub signature: Vec<u8>,
+}
+
+impl SignatureHeader {
+    pub fn parse(header: &str) -> Option<Self> {
+        let (mut key_id, mut timestamp, mut nonce, mut signature) = (None, None, None, None);
+
+        for field in header.split(',') {
+            let (name, value) = field.trim().split_once('=')?;
+
+            match name {
+                "key_id" => key_id = Some(value.to_string()),
+                "timestamp" => timestamp = Some(value.parse().ok()?),
+                "nonce" => nonce = Some(value.to_string()),
+                "signature" => signature = Some(STANDARD.decode(value).ok()?),
+                _ => return None,
+            }
+        }
+
+        let nonce = nonce.filter(|x| {
+            (16..=64).contains(&x.len())
+                && x.chars()
+                    .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_')
+        })?;
+
+        Some(Self {
+            key_id: key_id.filter(|x| !x.is_empty())?,
+            timestamp: timestamp?,
+            nonce,
+            signature: signature?,
+        })
+    }
+
+    pub fn is_fresh(&self, now: OffsetDateTime) -> bool {
+        OffsetDateTime::from_unix_timestamp(self.timestamp)
+            .is_ok_and(|x| (x - now).abs() <= MAX_CLOCK_SKEW)
+    }
+}
+
+/// The string that gets signed, the body is included as a hex SHA-256 digest.
+pub fn canonical_request(
+    method: &str,
+    path_and_query: &str,
+    timestamp: i64,
+    nonce: &str,
+    body: &[u8],
+) -> String {
+    let body_digest = Sha256::digest(body);
+
+    format!("{method}\n{path_and_query}\n{timestamp}\n{nonce}\n{body_digest:x}")
+}
+
+/// A server-side secret the signing keys are masked with in the database. It's not stored there, so
+/// a copy of the database alone can't be used to sign requests.
+pub struct Pepper(Vec<u8>);
+
+impl Pepper {
+    pub fn new(pepper: impl Into<Vec<u8>>) -> Self {
+        Self(pepper.into())
+    }
+
+    /// Reads the `request-signing/pepper` secret.
+    pub fn from_secret() -> Result<Self, secrets::Error> {
+        Ok(Self::new(
+            secrets::read("request-signing", "pepper")?.trim(),
+        ))
+    }
+
+    /// XORs the key with a mask derived from the pepper and the token's prefix, every prefix has a
+    /// single key. Masking the stored key again unmasks it.
+    fn mask(&self, prefix: &str, key: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        mac.update(b"signing key mask\n");
+        mac.update(prefix.as_bytes());
+        let mask = mac.finalize().into_bytes();
+
+        key.iter()
+            .zip(mask.iter().cycle())
+            .map(|(x, y)| x ^ y)
+            .collect()
+    }
+
+    pub fn seal(&self, prefix: &str, key: &SigningKey) -> Vec<u8> {
+        self.mask(prefix, &key.0)
+    }
+
+    pub fn open(&self, prefix: &str, sealed: &[u8]) -> SigningKey {
+        SigningKey(self.mask(prefix, sealed))
+    }
+}
+
+/// The key is derived from the token's secret, see [`super::token::signing_key`]. The secret itself
+/// never leaves the client.
+pub fn verify(key: &[u8], canonical_request: &str, signature: &[u8]) -> bool {
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(key) else {
+        return false;
+    };
+    mac.update(canonical_request.as_bytes());
+
+    mac.verify_slice(signature).is_ok()
+}
+
+/// Remembers the nonces of signed requests until their timestamps are too old to be accepted
+/// anyway. It's kept in the database, so that a request can't be replayed against another replica.
+pub struct NonceStore {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl NonceStore {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+
+    /// Returns `false` if the nonce was already used with this key.
+    pub async fn remember(
+        &self,
+        key_id: &str,
+        nonce: &str,
+        timestamp: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let expires_at = OffsetDateTime::from_unix_timestamp(timestamp)
+            .unwrap_or_else(|_| OffsetDateTime::now_utc())
+            + MAX_CLOCK_SKEW;
+
+        let result = sqlx::query!(
+            "INSERT INTO request_nonces (key_id, nonce, expires_at) VALUES ($1, $2, $3)
+                ON CONFLICT (key_id, nonce) DO UPDATE SET expires_at = EXCLUDED.expires_at
+                WHERE request_nonces.expires_at < now()",
+            key_id,
+            nonce,
+            expires_at
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Periodically removes the nonces that expired.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(NONCE_CLEANUP_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = sqlx::query!("DELETE FROM request_nonces WHERE expires_at < now()")
+                .execute(self.db_pool.as_ref())
+                .await
+            {
+                warn!("Failed to remove expired nonces: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sign(key: &[u8], canonical_request: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(canonical_request.as_bytes());
+
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    #[test]
+    pub fn can_parse_the_header() {
+        assert_eq!(
+            Some(SignatureHeader {
+                key_id: "prefix".to_string(),
+                timestamp: 1_700_000_000,
+                nonce: "0123456789abcdef".to_string(),
+                signature: vec![1, 2, 3],
+            }),
+            SignatureHeader::parse(
+                "key_id=prefix, timestamp=1700000000, nonce=0123456789abcdef, signature=AQID"
+            )
+        );
+
+        assert_eq!(
+            None,
+            SignatureHeader::parse("key_id=prefix, timestamp=1700000000, signature=AQID")
+        );
+        assert_eq!(
+            None,
+            SignatureHeader::parse(
+                "key_id=prefix, timestamp=1700000000, nonce=short, signature=AQID"
+            )
+        );
+    }
+
+    #[test]
+    pub fn seals_signing_keys() {
+        let key = super::super::token::signing_key("secret");
+        let pepper = Pepper::new("pepper");
+        let sealed = pepper.seal("prefix", &key);
+
+        assert_ne!(key.0, sealed);
+        assert_eq!(key, pepper.open("prefix", &sealed));
+        assert_ne!(key, pepper.open("other", &sealed));
+        assert_ne!(key, Pepper::new("other").open("prefix", &sealed));
+    }
+
+    #[test]
+    pub fn verifies_signatures() {
+        let canonical_request = canonical_request("POST", "/api/posts", 1, "nonce", b"{}");
+        let signature = sign(b"key", &canonical_request);
+
+        assert!(verify(b"key", &canonical_request, &signature));
+        assert!(!verify(b"other key", &canonical_request, &signature));
+        assert!(!verify(
+            b"key",
+            &canonical_request.replace("POST", "DELETE"),
+            &signature
+        ));
+    }
+
+    #[test]
+    pub fn rejects_old_timestamps() {
+        let now = OffsetDateTime::now_utc();
+        let mut header = SignatureHeader {
+            key_id: "prefix".to_string(),
+            timestamp: now.unix_timestamp(),
+            nonce: "0123456789abcdef".to_string(),
+            signature: vec![],
+        };
+
+        assert!(header.is_fresh(now));
+
+        header.timestamp -= MAX_CLOCK_SKEW.whole_seconds() + 1;
+        assert!(!header.is_fresh(now));
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use blog_core::api::SIGNING_KEY_LABEL;
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, CryptoRng, Rng};
+use sha2::{Digest, Sha256};
+
+const PREFIX_LENGTH: usize = 16;
+const SECRET_LENGTH: usize = 64;
+
+pub fn hash_secret(secret: &str) -> Vec<u8> {
+    Sha256::digest(secret.as_bytes()).to_vec()
+}
+
+/// The key the requests are signed with, derived from the secret, see [`SIGNING_KEY_LABEL`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct SigningKey(pub Vec<u8>);
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SigningKey(..)")
+    }
+}
+
+pub fn signing_key(secret: &str) -> SigningKey {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(SIGNING_KEY_LABEL.as_bytes());
+
+    SigningKey(mac.finalize().into_bytes().to_vec())
+}
+
+pub struct Generated {
+    pub prefix: String,
+    pub secret_hash: Vec<u8>,
+    pub signing_key: SigningKey,
+    /// The string that has to be presented to authenticate, in the `<prefix>.<secret>` format
+    pub content: String,
+}
+
+pub fn generate<TCryptoRng: CryptoRng + Rng>(csprng: impl FnOnce() -> TCryptoRng) -> Generated {
+    let mut csprng = csprng();
+    let mut random_string = |length| -> String {
+        (&mut csprng)
+            .sample_iter(&Alphanumeric)
+            .take(length)
+            .map(char::from)
+            .collect()
+    };
+
+    let prefix = random_string(PREFIX_LENGTH);
+    let secret = random_string(SECRET_LENGTH);
+    let content = format!("{prefix}.{secret}");
+
+    Generated {
+        prefix,
+        secret_hash: hash_secret(&secret),
+        signing_key: signing_key(&secret),
+        content,
+    }
+}
+
+/// Splits the token into the prefix and the secret. The tokens issued before they were hashed
+/// have no separator, for those the first characters are the prefix.
+pub fn split(token: &str) -> Option<(&str, &str)> {
+    let (prefix, secret) = token.split_once('.').or_else(|| {
+        token
+            .is_char_boundary(PREFIX_LENGTH)
+            .then(|| token.split_at(PREFIX_LENGTH))
+    })?;
+
+    if prefix.is_empty() || secret.is_empty() {
+        return None;
+    }
+
+    Some((prefix, secret))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn can_split_tokens() {
+        assert_eq!(Some(("abc", "def")), split("abc.def"));
+        assert_eq!(
+            Some(("0123456789abcdef", "rest")),
+            split("0123456789abcdefrest")
+        );
+        assert_eq!(None, split("short"));
+        assert_eq!(None, split(".secret"));
+        assert_eq!(None, split("prefix."));
+    }
+
+    #[test]
+    pub fn the_signing_key_is_not_the_hash() {
+        assert_ne!(hash_secret("secret"), signing_key("secret").0);
+        assert_eq!(signing_key("secret"), signing_key("secret"));
+        assert_ne!(signing_key("secret"), signing_key("other"));
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use sqlx::{Pool, Postgres};
+use time::OffsetDateTime;
+use tracing::warn;
+use uuid::Uuid;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Adds the usage to `pending`, keeping the latest time of every token.
+fn merge(
+    pending: &mut HashMap<Uuid, OffsetDateTime>,
+    usage: impl IntoIterator<Item = (Uuid, OffsetDateTime)>,
+) {
+    for (id, used_at) in usage {
+        let entry = pending.entry(id).or_insert(used_at);
+        *entry = (*entry).max(used_at);
+    }
+}
+
+/// Collects the times tokens were used at, and writes them to the database in batches, so that
+/// authentication doesn't have to wait for an extra query.
+pub struct TokenUsage {
+    db_pool: Arc<Pool<Postgres>>,
+    pending: Mutex<HashMap<Uuid, OffsetDateTime>>,
+}
+
+impl TokenUsage {
+    pub fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            db_pool,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, token_id: Uuid) {
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(token_id, OffsetDateTime::now_utc());
+    }
+
+    pub async fn flush(&self) -> Result<(), sqlx::Error> {
+        let pending = mem::take(&mut *self.pending.lock().unwrap());
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let (ids, used_at): (Vec<Uuid>, Vec<OffsetDateTime>) = pending.into_iter().unzip();
+
+        let result = sqlx::query!(
+            "UPDATE service_account_tokens AS t SET last_used_at = u.used_at
+                FROM UNNEST($1::uuid[], $2::timestamptz[]) AS u(id, used_at)
+                WHERE t.id = u.id AND (t.last_used_at IS NULL OR t.last_used_at < u.used_at)",
+            &ids,
+            &used_at
+        )
+        .execute(self.db_pool.as_ref())
+        .await;
+
+        // Kept for the next flush, unless the tokens were used again since
+        if let Err(e) = result {
+            merge(
+                &mut self.pending.lock().unwrap(),
+                ids.into_iter().zip(used_at),
+            );
+
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Periodically flushes the collected usage, the last flush has to be done by the caller after
+    /// the server stops.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.flush().await {
+                warn!("Failed to save token usage: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    pub fn merges_keeping_the_latest_use() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let mut pending = HashMap::from([(first, datetime!(2026-10-19 12:00 UTC))]);
+
+        merge(
+            &mut pending,
+            [
+                (first, datetime!(2026-10-19 11:00 UTC)),
+                (second, datetime!(2026-10-19 11:00 UTC)),
+            ],
+        );
+
+        assert_eq!(
+            HashMap::from([
+                (first, datetime!(2026-10-19 12:00 UTC)),
+                (second, datetime!(2026-10-19 11:00 UTC)),
+            ]),
+            pending
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+use tracing::info;
+
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    pub async fn wait(&self) {
+        let mut receiver = self.sender.subscribe();
+
+        // The sender lives as long as self, so this can't fail
+        let _ = receiver.wait_for(|shutting_down| *shutting_down).await;
+    }
+
+    /// Triggers the shutdown once the process receives SIGTERM or SIGINT.
+    pub async fn trigger_on_signal(self) {
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+
+        tokio::select! {
+            _ = terminate.recv() => info!("Received SIGTERM, shutting down"),
+            _ = tokio::signal::ctrl_c() => info!("Received SIGINT, shutting down"),
+        }
+
+        self.trigger();
+    }
+}
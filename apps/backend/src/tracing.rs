@@ -3,11 +3,13 @@ use std::collections::HashMap;
 use opentelemetry::{
     runtime,
     sdk::{trace::Tracer, Resource},
+    trace::TraceContextExt,
     KeyValue,
 };
 use opentelemetry_otlp::WithExportConfig;
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{
     filter::LevelFilter, prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt,
     Layer,
@@ -65,3 +67,23 @@ pub fn setup_tracing_subscriber() -> Result<(), SetupError> {
 
     Ok(())
 }
+
+/// The id of the trace the current span belongs to, if it's being exported.
+pub fn current_trace_id() -> Option<String> {
+    let context = Span::current().context();
+    let span_context = context.span().span_context().clone();
+
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/// Flushes the spans still waiting in the batch exporter.
+pub async fn shutdown() {
+    // Shutting down the provider blocks until the exporter is done, so it can't run on the runtime's threads
+    if let Err(e) =
+        tokio::task::spawn_blocking(opentelemetry::global::shutdown_tracer_provider).await
+    {
+        eprintln!("Failed to shutdown the tracer provider: {e}");
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        Path, State,
+    },
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    audit::AuditTarget,
+    error::{ApiError, Error},
+    service_accounts::{self, Authenticated, Scope},
+};
+
+use super::{User, UserRepository};
+
+#[derive(Serialize)]
+pub struct UserResponse {
+    id: Uuid,
+    name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+}
+
+impl UserResponse {
+    fn new(user: &User) -> Self {
+        Self {
+            id: user.id,
+            name: user.name.clone(),
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// The only response with the registration token, the user opens
+/// `/admin/register?token=<registration_token>` to register a passkey.
+#[derive(Serialize)]
+pub struct RegistrationResponse {
+    user: UserResponse,
+    registration_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct UserRequest {
+    name: String,
+}
+
+pub async fn route_api_get_users(
+    State(repository): State<Arc<UserRepository>>,
+) -> Result<Json<Vec<UserResponse>>, ApiError> {
+    let users = repository.find_all().await?;
+
+    Ok(Json(users.iter().map(UserResponse::new).collect()))
+}
+
+pub async fn route_api_post_users(
+    State(repository): State<Arc<UserRepository>>,
+    authenticated: Authenticated,
+    request: Result<Json<UserRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(request) = request?;
+    let name = request.name.trim();
+
+    if name.is_empty() {
+        return Err(Error::Validation("The name cannot be empty".to_string()).into());
+    }
+
+    if repository.find_by_name(name).await?.is_some() {
+        return Err(Error::Conflict(format!("A user named {name} already exists")).into());
+    }
+
+    // Checked again by the database, for users created in the meantime
+    let (user, registration_token) = repository.create(name.to_string()).await?;
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        created_user.id = %user.id,
+        "User created"
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Extension(AuditTarget(format!("/api/users/{}", user.id))),
+        Json(RegistrationResponse {
+            user: UserResponse::new(&user),
+            registration_token,
+        }),
+    ))
+}
+
+pub async fn route_api_delete_user(
+    State(repository): State<Arc<UserRepository>>,
+    authenticated: Authenticated,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Path(id) = id?;
+
+    if !repository.delete(id).await? {
+        return Err(Error::NotFound.into());
+    }
+
+    info!(
+        service_account.id = %authenticated.account().id(),
+        deleted_user.id = %id,
+        "User deleted"
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Issues a new registration token, for when the previous one expired, or the user needs another
+/// passkey. Any previous token stops working.
+pub async fn route_api_post_registration(
+    State(repository): State<Arc<UserRepository>>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<RegistrationResponse>, ApiError> {
+    let Path(id) = id?;
+    let user = repository.find_by_id(id).await?.ok_or(Error::NotFound)?;
+    let registration_token = repository
+        .issue_registration_token(id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok(Json(RegistrationResponse {
+        user: UserResponse::new(&user),
+        registration_token,
+    }))
+}
+
+/// The user management endpoints, all of them require the `accounts:admin` scope.
+pub fn router(repository: Arc<UserRepository>) -> Router {
+    Router::new()
+        .route(
+            "/users",
+            get(route_api_get_users).post(route_api_post_users),
+        )
+        .route("/users/:id", axum::routing::delete(route_api_delete_user))
+        .route("/users/:id/registration", post(route_api_post_registration))
+        .route_layer(axum::middleware::from_fn_with_state(
+            Scope::AccountsAdmin,
+            service_accounts::require_scope,
+        ))
+        .with_state(repository)
+}
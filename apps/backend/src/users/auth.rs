@@ -0,0 +1,446 @@
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{rejection::JsonRejection, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{types::Json as SqlJson, Pool, Postgres};
+use thiserror::Error;
+use time::{Duration, OffsetDateTime};
+use tracing::{debug, info};
+use uuid::Uuid;
+use webauthn_rs::{
+    prelude::{
+        CreationChallengeResponse, PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential,
+        RegisterPublicKeyCredential, RequestChallengeResponse, Url, WebauthnError,
+    },
+    Webauthn, WebauthnBuilder,
+};
+
+use crate::{
+    error::{ApiError, Error},
+    security_headers,
+};
+
+use super::{session, AuthenticatedUser, SessionStore, UserRepository};
+
+const DEFAULT_ORIGIN: &str = "http://localhost:8080";
+/// How long the browser has to finish a registration or a login once it's started.
+const CEREMONY_LIFETIME: Duration = Duration::minutes(5);
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Invalid WEBAUTHN_ORIGIN: {0}")]
+    InvalidOrigin(String),
+    #[error("Invalid WebAuthn configuration: {0}")]
+    Webauthn(#[from] WebauthnError),
+}
+
+#[derive(Clone, Copy)]
+enum Ceremony {
+    Registration,
+    Authentication,
+}
+
+impl Ceremony {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Registration => "registration",
+            Self::Authentication => "authentication",
+        }
+    }
+
+    /// The state of a stored ceremony, if it's one of this kind that didn't expire.
+    fn state<T: DeserializeOwned>(
+        self,
+        kind: &str,
+        expires_at: OffsetDateTime,
+        state: serde_json::Value,
+        now: OffsetDateTime,
+    ) -> Option<T> {
+        if kind != self.as_str() || expires_at <= now {
+            return None;
+        }
+
+        serde_json::from_value(state).ok()
+    }
+}
+
+fn webauthn(origin: &str) -> Result<Webauthn, ConfigError> {
+    let origin_url =
+        Url::parse(origin).map_err(|_| ConfigError::InvalidOrigin(origin.to_string()))?;
+    let rp_id = origin_url
+        .host_str()
+        .ok_or_else(|| ConfigError::InvalidOrigin(origin.to_string()))?;
+
+    Ok(WebauthnBuilder::new(rp_id, &origin_url)?
+        .rp_name(rp_id)
+        .build()?)
+}
+
+/// Registration and login of users with passkeys. The state of the ceremonies is kept in the
+/// database between the start and the finish, so that they work across replicas.
+pub struct PasskeyAuth {
+    webauthn: Webauthn,
+    db_pool: Arc<Pool<Postgres>>,
+    users: Arc<UserRepository>,
+    sessions: Arc<SessionStore>,
+}
+
+impl PasskeyAuth {
+    /// Reads `WEBAUTHN_ORIGIN`, the origin the admin is served from (`http://localhost:8080` by
+    /// default). Its host is the relying party ID, so passkeys are bound to it.
+    pub fn from_env(
+        db_pool: Arc<Pool<Postgres>>,
+        users: Arc<UserRepository>,
+        sessions: Arc<SessionStore>,
+    ) -> Result<Self, ConfigError> {
+        let origin =
+            std::env::var("WEBAUTHN_ORIGIN").unwrap_or_else(|_| DEFAULT_ORIGIN.to_string());
+
+        Ok(Self {
+            webauthn: webauthn(&origin)?,
+            db_pool,
+            users,
+            sessions,
+        })
+    }
+
+    async fn start_ceremony<T: Serialize + Sync>(
+        &self,
+        kind: Ceremony,
+        user_id: Uuid,
+        state: &T,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query!("DELETE FROM webauthn_ceremonies WHERE expires_at < now()")
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "INSERT INTO webauthn_ceremonies (id, user_id, kind, state, expires_at) VALUES ($1, $2, $3, $4, $5)",
+            id,
+            user_id,
+            kind.as_str(),
+            SqlJson(state) as _,
+            OffsetDateTime::now_utc() + CEREMONY_LIFETIME
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(id)
+    }
+
+    /// A ceremony can be finished only once, it's removed here whether it succeeds or not.
+    async fn take_ceremony<T: DeserializeOwned>(
+        &self,
+        kind: Ceremony,
+        id: Uuid,
+    ) -> Result<Option<(Uuid, T)>, sqlx::Error> {
+        let ceremony = sqlx::query!(
+            "DELETE FROM webauthn_ceremonies WHERE id = $1 RETURNING user_id, kind, state, expires_at",
+            id
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await?;
+
+        Ok(ceremony.and_then(|x| {
+            let state = kind.state(&x.kind, x.expires_at, x.state, OffsetDateTime::now_utc())?;
+
+            Some((x.user_id, state))
+        }))
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/login.html")]
+struct LoginTemplate {
+    csp_nonce: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/register.html")]
+struct RegisterTemplate {
+    token: String,
+    csp_nonce: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/index.html")]
+struct IndexTemplate {
+    name: String,
+    csrf_token: String,
+    csp_nonce: String,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterQuery {
+    #[serde(default)]
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct RegistrationStartRequest {
+    token: String,
+}
+
+#[derive(Serialize)]
+pub struct RegistrationStartResponse {
+    ceremony_id: Uuid,
+    options: CreationChallengeResponse,
+}
+
+#[derive(Deserialize)]
+pub struct RegistrationFinishRequest {
+    ceremony_id: Uuid,
+    credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct LoginStartRequest {
+    name: String,
+}
+
+#[derive(Serialize)]
+pub struct LoginStartResponse {
+    ceremony_id: Uuid,
+    options: RequestChallengeResponse,
+}
+
+#[derive(Deserialize)]
+pub struct LoginFinishRequest {
+    ceremony_id: Uuid,
+    credential: PublicKeyCredential,
+}
+
+pub async fn route_get_login() -> Result<impl IntoResponse, Error> {
+    let template = LoginTemplate {
+        csp_nonce: security_headers::nonce(),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn route_get_register(
+    Query(query): Query<RegisterQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let template = RegisterTemplate {
+        token: query.token,
+        csp_nonce: security_headers::nonce(),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn route_post_register_start(
+    State(auth): State<Arc<PasskeyAuth>>,
+    request: Result<Json<RegistrationStartRequest>, JsonRejection>,
+) -> Result<Json<RegistrationStartResponse>, ApiError> {
+    let Json(request) = request?;
+    let user = auth
+        .users
+        .find_by_registration_token(&request.token)
+        .await?
+        .ok_or(Error::Unauthorized)?;
+
+    let existing = auth
+        .users
+        .find_passkeys(user.id)
+        .await?
+        .iter()
+        .map(|x| x.cred_id().clone())
+        .collect();
+    let (options, state) = auth.webauthn.start_passkey_registration(
+        user.id,
+        &user.name,
+        &user.name,
+        Some(existing),
+    )?;
+
+    let ceremony_id = auth
+        .start_ceremony(Ceremony::Registration, user.id, &state)
+        .await?;
+
+    Ok(Json(RegistrationStartResponse {
+        ceremony_id,
+        options,
+    }))
+}
+
+pub async fn route_post_register_finish(
+    State(auth): State<Arc<PasskeyAuth>>,
+    request: Result<Json<RegistrationFinishRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(request) = request?;
+    let (user_id, state) = auth
+        .take_ceremony::<PasskeyRegistration>(Ceremony::Registration, request.ceremony_id)
+        .await?
+        .ok_or(Error::Unauthorized)?;
+
+    let passkey = auth
+        .webauthn
+        .finish_passkey_registration(&request.credential, &state)
+        .map_err(|e| {
+            debug!("Passkey registration failed: {}", e);
+            Error::Unauthorized
+        })?;
+
+    if !auth.users.add_passkey(user_id, &passkey).await? {
+        return Err(Error::Conflict(
+            "The registration link was already used, or the passkey is already registered"
+                .to_string(),
+        )
+        .into());
+    }
+
+    info!(user.id = %user_id, "Passkey registered");
+
+    let cookie = auth.sessions.create(user_id).await?;
+
+    Ok((StatusCode::NO_CONTENT, [(header::SET_COOKIE, cookie)]))
+}
+
+pub async fn route_post_login_start(
+    State(auth): State<Arc<PasskeyAuth>>,
+    request: Result<Json<LoginStartRequest>, JsonRejection>,
+) -> Result<Json<LoginStartResponse>, ApiError> {
+    let Json(request) = request?;
+    let user = auth
+        .users
+        .find_by_name(request.name.trim())
+        .await?
+        .ok_or(Error::Unauthorized)?;
+
+    let passkeys = auth.users.find_passkeys(user.id).await?;
+    if passkeys.is_empty() {
+        return Err(Error::Unauthorized.into());
+    }
+
+    let (options, state) = auth.webauthn.start_passkey_authentication(&passkeys)?;
+    let ceremony_id = auth
+        .start_ceremony(Ceremony::Authentication, user.id, &state)
+        .await?;
+
+    Ok(Json(LoginStartResponse {
+        ceremony_id,
+        options,
+    }))
+}
+
+pub async fn route_post_login_finish(
+    State(auth): State<Arc<PasskeyAuth>>,
+    request: Result<Json<LoginFinishRequest>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(request) = request?;
+    let (user_id, state) = auth
+        .take_ceremony::<PasskeyAuthentication>(Ceremony::Authentication, request.ceremony_id)
+        .await?
+        .ok_or(Error::Unauthorized)?;
+
+    let result = auth
+        .webauthn
+        .finish_passkey_authentication(&request.credential, &state)
+        .map_err(|e| {
+            debug!("Passkey authentication failed: {}", e);
+            Error::Unauthorized
+        })?;
+    auth.users.record_passkey_use(user_id, &result).await?;
+
+    info!(user.id = %user_id, "User logged in");
+
+    let cookie = auth.sessions.create(user_id).await?;
+
+    Ok((StatusCode::NO_CONTENT, [(header::SET_COOKIE, cookie)]))
+}
+
+pub async fn route_get_index(user: AuthenticatedUser) -> Result<impl IntoResponse, Error> {
+    let template = IndexTemplate {
+        name: user.user().name().to_string(),
+        csrf_token: user.csrf_token().to_string(),
+        csp_nonce: security_headers::nonce(),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn route_post_logout(
+    State(auth): State<Arc<PasskeyAuth>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, Error> {
+    let cookie = auth.sessions.delete(&headers).await?;
+
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Redirect::to(session::LOGIN_PATH),
+    ))
+}
+
+/// The login and registration pages, and the pages that require a session.
+pub fn router(auth: Arc<PasskeyAuth>) -> Router {
+    let sessions = auth.sessions.clone();
+
+    Router::new()
+        .route("/admin", get(route_get_index))
+        .route("/admin/logout", post(route_post_logout))
+        .route_layer(axum::middleware::from_fn_with_state(
+            sessions,
+            session::middleware,
+        ))
+        .route(session::LOGIN_PATH, get(route_get_login))
+        .route("/admin/login/start", post(route_post_login_start))
+        .route("/admin/login/finish", post(route_post_login_finish))
+        .route("/admin/register", get(route_get_register))
+        .route("/admin/register/start", post(route_post_register_start))
+        .route("/admin/register/finish", post(route_post_register_finish))
+        .with_state(auth)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn relying_party_is_the_origin_host() {
+        assert!(webauthn(DEFAULT_ORIGIN).is_ok());
+        assert!(webauthn("https://ramona.fun").is_ok());
+        assert!(matches!(
+            webauthn("ramona.fun"),
+            Err(ConfigError::InvalidOrigin(_))
+        ));
+        assert!(matches!(
+            webauthn("data:text/plain,hello"),
+            Err(ConfigError::InvalidOrigin(_))
+        ));
+    }
+
+    #[test]
+    pub fn ceremonies_can_be_finished_only_as_started() {
+        let webauthn = webauthn(DEFAULT_ORIGIN).unwrap();
+        let user_id = Uuid::new_v4();
+        let (_, registration) = webauthn
+            .start_passkey_registration(user_id, "ramona", "ramona", None)
+            .unwrap();
+        let stored = serde_json::to_value(&registration).unwrap();
+        let now = OffsetDateTime::now_utc();
+        let expires_at = now + CEREMONY_LIFETIME;
+
+        assert!(Ceremony::Registration
+            .state::<PasskeyRegistration>("registration", expires_at, stored.clone(), now)
+            .is_some());
+        assert!(Ceremony::Authentication
+            .state::<PasskeyAuthentication>("registration", expires_at, stored.clone(), now)
+            .is_none());
+        assert!(Ceremony::Registration
+            .state::<PasskeyRegistration>("registration", expires_at, stored.clone(), expires_at)
+            .is_none());
+        // A state of another kind, e.g. one stored by an older version, is rejected
+        assert!(Ceremony::Authentication
+            .state::<PasskeyAuthentication>("authentication", expires_at, stored, now)
+            .is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::{thread_rng, RngCore};
+use sha2::{Digest, Sha256};
+use sqlx::{types::Json, Pool, Postgres};
+use thiserror::Error;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+use webauthn_rs::prelude::{AuthenticationResult, Passkey};
+
+pub use self::session::{AuthenticatedUser, SessionStore};
+
+pub mod api;
+pub mod auth;
+pub mod session;
+
+/// How long the link for registering the first passkey can be used.
+const REGISTRATION_TOKEN_LIFETIME: Duration = Duration::days(1);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Database Error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("A user named {0} already exists")]
+    DuplicateName(String),
+}
+
+impl Error {
+    fn from_sqlx(error: sqlx::Error, name: &str) -> Self {
+        match &error {
+            sqlx::Error::Database(e) if e.constraint() == Some("users_name_key") => {
+                Self::DuplicateName(name.to_string())
+            }
+            _ => Self::Sqlx(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct User {
+    id: Uuid,
+    name: String,
+    created_at: OffsetDateTime,
+}
+
+impl User {
+    pub const fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Returns a random URL-safe token, and its hash, which is what gets stored.
+fn generate_token() -> (String, Vec<u8>) {
+    let mut bytes = [0u8; 32];
+    thread_rng().fill_bytes(&mut bytes);
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+    let hash = hash_token(&token);
+
+    (token, hash)
+}
+
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+/// Whether a passkey can still be registered with the token, it's cleared once it was used.
+fn is_registration_open(expires_at: Option<OffsetDateTime>, now: OffsetDateTime) -> bool {
+    expires_at.is_some_and(|x| x > now)
+}
+
+pub struct UserRepository {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl UserRepository {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<User>, sqlx::Error> {
+        sqlx::query_as!(User, "SELECT id, name, created_at FROM users ORDER BY name")
+            .fetch_all(self.db_pool.as_ref())
+            .await
+    }
+
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as!(
+            User,
+            "SELECT id, name, created_at FROM users WHERE id = $1",
+            id
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await
+    }
+
+    pub async fn find_by_name(&self, name: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as!(
+            User,
+            "SELECT id, name, created_at FROM users WHERE name = $1",
+            name
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await
+    }
+
+    /// Returns the new user, and the token for registering their first passkey.
+    pub async fn create(&self, name: String) -> Result<(User, String), Error> {
+        let (token, token_hash) = generate_token();
+        let user = sqlx::query_as!(
+            User,
+            "INSERT INTO users (id, name, registration_token_hash, registration_expires_at)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, name, created_at",
+            Uuid::new_v4(),
+            name,
+            token_hash,
+            OffsetDateTime::now_utc() + REGISTRATION_TOKEN_LIFETIME
+        )
+        .fetch_one(self.db_pool.as_ref())
+        .await
+        .map_err(|e| Error::from_sqlx(e, &name))?;
+
+        Ok((user, token))
+    }
+
+    /// Replaces the registration token of the user, e.g. when the previous one expired or another
+    /// passkey has to be added. Returns `None` if there's no such user.
+    pub async fn issue_registration_token(&self, id: Uuid) -> Result<Option<String>, sqlx::Error> {
+        let (token, token_hash) = generate_token();
+        let result = sqlx::query!(
+            "UPDATE users SET registration_token_hash = $2, registration_expires_at = $3 WHERE id = $1",
+            id,
+            token_hash,
+            OffsetDateTime::now_utc() + REGISTRATION_TOKEN_LIFETIME
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok((result.rows_affected() > 0).then_some(token))
+    }
+
+    pub async fn find_by_registration_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query!(
+            "SELECT id, name, created_at, registration_expires_at FROM users
+                WHERE registration_token_hash = $1",
+            hash_token(token)
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await?;
+
+        Ok(user
+            .filter(|x| is_registration_open(x.registration_expires_at, OffsetDateTime::now_utc()))
+            .map(|x| User {
+                id: x.id,
+                name: x.name,
+                created_at: x.created_at,
+            }))
+    }
+
+    pub async fn delete(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM users WHERE id = $1", id)
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn find_passkeys(&self, user_id: Uuid) -> Result<Vec<Passkey>, sqlx::Error> {
+        let passkeys = sqlx::query_scalar!(
+            r#"SELECT passkey AS "passkey: Json<Passkey>" FROM user_passkeys WHERE user_id = $1"#,
+            user_id
+        )
+        .fetch_all(self.db_pool.as_ref())
+        .await?;
+
+        Ok(passkeys.into_iter().map(|x| x.0).collect())
+    }
+
+    /// Also uses up the registration token. Returns `false` if the token was used up or expired in
+    /// the meantime, or if the credential is already registered, to this or any other user.
+    pub async fn add_passkey(&self, user_id: Uuid, passkey: &Passkey) -> Result<bool, sqlx::Error> {
+        let mut transaction = self.db_pool.begin().await?;
+
+        let expires_at = sqlx::query_scalar!(
+            "SELECT registration_expires_at FROM users WHERE id = $1 FOR UPDATE",
+            user_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .flatten();
+
+        if !is_registration_open(expires_at, OffsetDateTime::now_utc()) {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            "UPDATE users SET registration_token_hash = NULL, registration_expires_at = NULL
+                WHERE id = $1",
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let result = sqlx::query!(
+            "INSERT INTO user_passkeys (id, user_id, credential_id, passkey) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (credential_id) DO NOTHING",
+            Uuid::new_v4(),
+            user_id,
+            passkey.cred_id().as_ref(),
+            Json(passkey) as _
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        transaction.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Stores the signature counter and the backup state reported by the authenticator on login.
+    pub async fn record_passkey_use(
+        &self,
+        user_id: Uuid,
+        result: &AuthenticationResult,
+    ) -> Result<(), sqlx::Error> {
+        let passkey = sqlx::query_scalar!(
+            r#"SELECT passkey AS "passkey: Json<Passkey>" FROM user_passkeys
+                WHERE user_id = $1 AND credential_id = $2"#,
+            user_id,
+            result.cred_id().as_ref()
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await?;
+
+        let Some(Json(mut passkey)) = passkey else {
+            return Ok(());
+        };
+        passkey.update_credential(result);
+
+        sqlx::query!(
+            "UPDATE user_passkeys SET passkey = $3, last_used_at = now()
+                WHERE user_id = $1 AND credential_id = $2",
+            user_id,
+            result.cred_id().as_ref(),
+            Json(&passkey) as _
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn tokens_are_stored_hashed() {
+        let (token, hash) = generate_token();
+        let (other_token, _) = generate_token();
+
+        assert_ne!(token, other_token);
+        assert_eq!(hash, hash_token(&token));
+        assert_ne!(hash, token.as_bytes());
+    }
+
+    #[test]
+    pub fn registration_closes_when_the_token_expires_or_is_used() {
+        let now = OffsetDateTime::now_utc();
+
+        assert!(is_registration_open(
+            Some(now + REGISTRATION_TOKEN_LIFETIME),
+            now
+        ));
+        assert!(!is_registration_open(Some(now), now));
+        assert!(!is_registration_open(
+            Some(now + REGISTRATION_TOKEN_LIFETIME),
+            now + REGISTRATION_TOKEN_LIFETIME
+        ));
+        // add_passkey clears the expiry along with the token
+        assert!(!is_registration_open(None, now));
+    }
+}
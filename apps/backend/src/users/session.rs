@@ -0,0 +1,279 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use http_body::Limited;
+use sqlx::{Pool, Postgres};
+use subtle::ConstantTimeEq;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::error::Error;
+
+use super::{generate_token, hash_token, User};
+
+/// The `__Host-` prefix makes browsers accept the cookie only if it's `Secure`, has no `Domain`
+/// and is set for `/`.
+pub const SESSION_COOKIE: &str = "__Host-session";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+/// The form field with the CSRF token, for forms that are submitted without JavaScript.
+pub const CSRF_FIELD: &str = "csrf_token";
+pub const LOGIN_PATH: &str = "/admin/login";
+
+const SESSION_LIFETIME: Duration = Duration::hours(12);
+/// Forms are buffered whole to find the CSRF token.
+const MAX_FORM_SIZE: usize = 4 * 1024 * 1024;
+
+/// The user of the current session, set by [`middleware`].
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    user: User,
+    csrf_token: String,
+}
+
+impl AuthenticatedUser {
+    pub const fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Has to be sent with every request that changes something, either in the `X-CSRF-Token`
+    /// header or in the `csrf_token` form field.
+    pub fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthenticatedUser {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or(Error::Unauthorized)
+    }
+}
+
+/// Sessions are kept in the database, the cookie only has a random token, whose hash is the key.
+pub struct SessionStore {
+    db_pool: Arc<Pool<Postgres>>,
+}
+
+impl SessionStore {
+    pub const fn new(db_pool: Arc<Pool<Postgres>>) -> Self {
+        Self { db_pool }
+    }
+
+    /// Returns the `Set-Cookie` header value for the new session.
+    pub async fn create(&self, user_id: Uuid) -> Result<HeaderValue, sqlx::Error> {
+        // There are few sessions, so the expired ones are removed here instead of periodically
+        sqlx::query!("DELETE FROM user_sessions WHERE expires_at < now()")
+            .execute(self.db_pool.as_ref())
+            .await?;
+
+        let (token, token_hash) = generate_token();
+        let (csrf_token, _) = generate_token();
+
+        sqlx::query!(
+            "INSERT INTO user_sessions (token_hash, user_id, csrf_token, expires_at) VALUES ($1, $2, $3, $4)",
+            token_hash,
+            user_id,
+            csrf_token,
+            OffsetDateTime::now_utc() + SESSION_LIFETIME
+        )
+        .execute(self.db_pool.as_ref())
+        .await?;
+
+        Ok(session_cookie(&token, SESSION_LIFETIME))
+    }
+
+    async fn find(&self, token: &str) -> Result<Option<AuthenticatedUser>, sqlx::Error> {
+        let session = sqlx::query!(
+            "SELECT users.id, users.name, users.created_at, user_sessions.csrf_token
+                FROM user_sessions JOIN users ON users.id = user_sessions.user_id
+                WHERE user_sessions.token_hash = $1 AND user_sessions.expires_at > now()",
+            hash_token(token)
+        )
+        .fetch_optional(self.db_pool.as_ref())
+        .await?;
+
+        Ok(session.map(|x| AuthenticatedUser {
+            user: User {
+                id: x.id,
+                name: x.name,
+                created_at: x.created_at,
+            },
+            csrf_token: x.csrf_token,
+        }))
+    }
+
+    /// Returns the `Set-Cookie` header value that removes the cookie.
+    pub async fn delete(&self, headers: &HeaderMap) -> Result<HeaderValue, sqlx::Error> {
+        if let Some(token) = session_token(headers) {
+            sqlx::query!(
+                "DELETE FROM user_sessions WHERE token_hash = $1",
+                hash_token(token)
+            )
+            .execute(self.db_pool.as_ref())
+            .await?;
+        }
+
+        Ok(session_cookie("", Duration::ZERO))
+    }
+}
+
+fn session_cookie(token: &str, max_age: Duration) -> HeaderValue {
+    HeaderValue::from_str(&format!(
+        "{SESSION_COOKIE}={token}; Path=/; Max-Age={}; Secure; HttpOnly; SameSite=Lax",
+        max_age.whole_seconds()
+    ))
+    .expect("The session cookie is a valid header value")
+}
+
+fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|x| x.to_str().ok())
+        .flat_map(|x| x.split(';'))
+        .filter_map(|x| x.trim().split_once('='))
+        .find(|(name, value)| *name == SESSION_COOKIE && !value.is_empty())
+        .map(|(_, value)| value)
+}
+
+fn is_valid_csrf_token(user: &AuthenticatedUser, token: &[u8]) -> bool {
+    token.ct_eq(user.csrf_token.as_bytes()).into()
+}
+
+async fn verify_csrf(
+    user: &AuthenticatedUser,
+    request: Request<Body>,
+) -> Result<Request<Body>, Error> {
+    let invalid = || Error::Forbidden("The CSRF token is missing or invalid".to_string());
+
+    if let Some(token) = request.headers().get(CSRF_HEADER) {
+        return if is_valid_csrf_token(user, token.as_bytes()) {
+            Ok(request)
+        } else {
+            Err(invalid())
+        };
+    }
+
+    let is_form = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|x| x.starts_with("application/x-www-form-urlencoded"));
+    if !is_form {
+        return Err(invalid());
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(Limited::new(body, MAX_FORM_SIZE))
+        .await
+        .map_err(|_| Error::Validation("The request body is too large".to_string()))?;
+
+    if !form_urlencoded::parse(&body)
+        .any(|(name, value)| name == CSRF_FIELD && is_valid_csrf_token(user, value.as_bytes()))
+    {
+        return Err(invalid());
+    }
+
+    Ok(Request::from_parts(parts, Body::from(body)))
+}
+
+/// Requires a session, the pages redirect to the login page without one. Requests that change
+/// something also need the session's CSRF token.
+pub async fn middleware(
+    State(sessions): State<Arc<SessionStore>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, Error> {
+    let user = match session_token(request.headers()) {
+        Some(token) => sessions.find(token).await?,
+        None => None,
+    };
+
+    let Some(user) = user else {
+        if matches!(*request.method(), Method::GET | Method::HEAD) {
+            return Ok(Redirect::to(LOGIN_PATH).into_response());
+        }
+
+        return Err(Error::Unauthorized);
+    };
+
+    let mut request = if matches!(*request.method(), Method::GET | Method::HEAD) {
+        request
+    } else {
+        verify_csrf(&user, request).await?
+    };
+
+    request.extensions_mut().insert(user);
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn user() -> AuthenticatedUser {
+        AuthenticatedUser {
+            user: User {
+                id: Uuid::new_v4(),
+                name: "ramona".to_string(),
+                created_at: OffsetDateTime::now_utc(),
+            },
+            csrf_token: "csrf".to_string(),
+        }
+    }
+
+    #[test]
+    pub fn can_read_the_session_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; __Host-session=token; other=1"),
+        );
+
+        assert_eq!(Some("token"), session_token(&headers));
+
+        headers.insert(header::COOKIE, HeaderValue::from_static("session=token"));
+        assert_eq!(None, session_token(&headers));
+    }
+
+    #[tokio::test]
+    pub async fn requires_a_csrf_token() {
+        let request = |content_type, body: &'static str| {
+            Request::post("/admin/logout")
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let form = "application/x-www-form-urlencoded";
+
+        assert!(verify_csrf(&user(), request(form, "csrf_token=csrf"))
+            .await
+            .is_ok());
+        assert!(verify_csrf(&user(), request(form, "csrf_token=other"))
+            .await
+            .is_err());
+        assert!(verify_csrf(&user(), request("application/json", "{}"))
+            .await
+            .is_err());
+
+        let mut with_header = request("application/json", "{}");
+        with_header
+            .headers_mut()
+            .insert(CSRF_HEADER, HeaderValue::from_static("csrf"));
+        assert!(verify_csrf(&user(), with_header).await.is_ok());
+    }
+}
@@ -0,0 +1,87 @@
+const decode = (value) => Uint8Array.from(
+    atob(value.replace(/-/g, '+').replace(/_/g, '/')),
+    (c) => c.charCodeAt(0)
+);
+const encode = (buffer) => btoa(String.fromCharCode(...new Uint8Array(buffer)))
+    .replace(/\+/g, '-')
+    .replace(/\//g, '_')
+    .replace(/=+$/, '');
+
+async function postJson(url, body) {
+    const response = await fetch(url, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(body),
+    });
+
+    if (!response.ok) {
+        const problem = await response.json().catch(() => ({}));
+        throw new Error(problem.detail || problem.title || response.statusText);
+    }
+
+    return response.status === 204 ? null : response.json();
+}
+
+async function register(token) {
+    const { ceremony_id, options } = await postJson('/admin/register/start', { token });
+    const publicKey = options.publicKey;
+    publicKey.challenge = decode(publicKey.challenge);
+    publicKey.user.id = decode(publicKey.user.id);
+    (publicKey.excludeCredentials || []).forEach((x) => { x.id = decode(x.id); });
+
+    const credential = await navigator.credentials.create({ publicKey });
+
+    await postJson('/admin/register/finish', {
+        ceremony_id,
+        credential: {
+            id: credential.id,
+            rawId: encode(credential.rawId),
+            type: credential.type,
+            response: {
+                attestationObject: encode(credential.response.attestationObject),
+                clientDataJSON: encode(credential.response.clientDataJSON),
+            },
+        },
+    });
+}
+
+async function login(name) {
+    const { ceremony_id, options } = await postJson('/admin/login/start', { name });
+    const publicKey = options.publicKey;
+    publicKey.challenge = decode(publicKey.challenge);
+    (publicKey.allowCredentials || []).forEach((x) => { x.id = decode(x.id); });
+
+    const credential = await navigator.credentials.get({ publicKey });
+    const userHandle = credential.response.userHandle;
+
+    await postJson('/admin/login/finish', {
+        ceremony_id,
+        credential: {
+            id: credential.id,
+            rawId: encode(credential.rawId),
+            type: credential.type,
+            response: {
+                authenticatorData: encode(credential.response.authenticatorData),
+                clientDataJSON: encode(credential.response.clientDataJSON),
+                signature: encode(credential.response.signature),
+                userHandle: userHandle ? encode(userHandle) : null,
+            },
+        },
+    });
+}
+
+function handleSubmit(form, action) {
+    const error = form.querySelector('.error');
+
+    form.addEventListener('submit', async (event) => {
+        event.preventDefault();
+        error.textContent = '';
+
+        try {
+            await action();
+            window.location.href = '/admin';
+        } catch (e) {
+            error.textContent = e.message;
+        }
+    });
+}
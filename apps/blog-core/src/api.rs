@@ -0,0 +1,355 @@
+//! The JSON of the posts API, as the backend sends and accepts it.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Signed requests use the HMAC-SHA256 of this label, keyed with the token's secret, as the key.
+///
+/// It differs from the hash of the secret the server checks `X-Token` against, so that hash can't
+/// be used to sign requests.
+pub const SIGNING_KEY_LABEL: &str = "blog request signing v1";
+
+/// Hex encoded SHA-256 of the markdown, the lists have it instead of the content.
+#[must_use]
+pub fn content_sha256(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// The media files are addressed by the hex encoded SHA-256 of their content, so the same file is
+/// only stored once, and the URL of a file never serves anything else.
+#[must_use]
+pub fn media_sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Where the blog serves the media file, the posts link to it with this path.
+#[must_use]
+pub fn media_path(sha256: &str) -> String {
+    format!("/media/{sha256}")
+}
+
+/// The file extensions of the media the blog accepts, with the content type it's served with.
+pub const MEDIA_TYPES: [(&str, &str); 9] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("pdf", "application/pdf"),
+    ("mp4", "video/mp4"),
+    ("txt", "text/plain"),
+];
+
+/// The content type of a file with the extension, `None` if the blog doesn't accept it.
+#[must_use]
+pub fn media_type(extension: &str) -> Option<&'static str> {
+    MEDIA_TYPES
+        .iter()
+        .find(|(x, _)| x.eq_ignore_ascii_case(extension))
+        .map(|(_, content_type)| *content_type)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostResponse {
+    pub id: Uuid,
+    pub title: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub date_published: OffsetDateTime,
+    /// `false` for the posts scheduled for later
+    pub published: bool,
+    pub content: String,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// A post in a list, with only the hash of the content, which is enough to tell whether a local
+/// copy differs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostSummaryResponse {
+    pub id: Uuid,
+    pub title: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub date_published: OffsetDateTime,
+    pub published: bool,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// See [`content_sha256`]
+    pub content_sha256: String,
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostsQuery {
+    /// Lists the deleted posts instead, to find the ones to restore
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostCreateRequest {
+    pub id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// The whole post, for creating or replacing it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostPutRequest {
+    pub title: String,
+    pub content: String,
+    /// Now if not set, or unchanged for an existing post
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub date_published: Option<OffsetDateTime>,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Only the fields that are set are changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostUpdateRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Can be in the future, to schedule the post
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub date_published: Option<OffsetDateTime>,
+}
+
+/// What's being written, it's saved separately from the post, which it doesn't change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DraftPutRequest {
+    pub title: String,
+    pub content: String,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DraftResponse {
+    pub id: Uuid,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+/// A post to render like the public page, without saving it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviewRequest {
+    pub title: String,
+    pub content: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaResponse {
+    pub sha256: String,
+    pub content_type: String,
+    pub size: u64,
+    /// See [`media_path`]
+    pub path: String,
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use serde::de::DeserializeOwned;
+    use serde_json::{json, Value};
+    use time::macros::datetime;
+
+    use super::*;
+
+    const ID: &str = "5f0c1c2e-1111-4222-8333-944455556666";
+
+    /// The JSON has to parse into `expected`, and serialize back to the same JSON.
+    fn assert_round_trip<T>(json: &Value, expected: &T)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let parsed: T = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(expected, &parsed);
+        assert_eq!(json, &serde_json::to_value(&parsed).unwrap());
+    }
+
+    /// The responses are in files, the backend tests that it produces the same JSON.
+    fn fixture(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    pub fn posts_round_trip() {
+        assert_round_trip(
+            &fixture(include_str!("../fixtures/post.json")),
+            &PostResponse {
+                id: ID.parse().unwrap(),
+                title: "Hello".to_string(),
+                date_published: datetime!(2026-10-19 10:00 UTC),
+                published: true,
+                content: "# Hello".to_string(),
+                slug: Some("hello".to_string()),
+                description: None,
+                tags: vec!["meta".to_string()],
+            },
+        );
+
+        let summary = PostSummaryResponse {
+            id: ID.parse().unwrap(),
+            title: "Hello".to_string(),
+            date_published: datetime!(2026-10-19 10:00 UTC),
+            published: true,
+            slug: None,
+            description: Some("About".to_string()),
+            tags: vec![],
+            content_sha256: content_sha256("# Hello"),
+            deleted_at: None,
+        };
+        assert_round_trip(
+            &fixture(include_str!("../fixtures/post_summary.json")),
+            &summary,
+        );
+        assert_round_trip(
+            &fixture(include_str!("../fixtures/deleted_post_summary.json")),
+            &PostSummaryResponse {
+                deleted_at: Some(datetime!(2026-10-20 08:30 UTC)),
+                ..summary
+            },
+        );
+
+        assert_round_trip(
+            &json!({ "id": ID, "updated_at": "2026-10-19T10:00:00Z" }),
+            &DraftResponse {
+                id: ID.parse().unwrap(),
+                updated_at: datetime!(2026-10-19 10:00 UTC),
+            },
+        );
+
+        let sha256 = media_sha256(b"");
+        assert_round_trip(
+            &json!({
+                "sha256": sha256,
+                "content_type": "image/png",
+                "size": 0,
+                "path": media_path(&sha256)
+            }),
+            &MediaResponse {
+                sha256: sha256.clone(),
+                content_type: "image/png".to_string(),
+                size: 0,
+                path: format!("/media/{sha256}"),
+            },
+        );
+    }
+
+    #[test]
+    pub fn requests_round_trip() {
+        assert_round_trip(
+            &json!({
+                "title": "Hello",
+                "content": "# Hello",
+                "date_published": "2026-10-19T10:00:00Z",
+                "slug": "hello",
+                "description": null,
+                "tags": ["meta"]
+            }),
+            &PostPutRequest {
+                title: "Hello".to_string(),
+                content: "# Hello".to_string(),
+                date_published: Some(datetime!(2026-10-19 10:00 UTC)),
+                slug: Some("hello".to_string()),
+                description: None,
+                tags: vec!["meta".to_string()],
+            },
+        );
+
+        assert_round_trip(
+            &json!({ "title": "Renamed" }),
+            &PostUpdateRequest {
+                title: Some("Renamed".to_string()),
+                ..PostUpdateRequest::default()
+            },
+        );
+
+        assert_round_trip(
+            &json!({
+                "title": "Hello",
+                "content": "# Hello",
+                "slug": null,
+                "description": "About",
+                "tags": []
+            }),
+            &DraftPutRequest {
+                title: "Hello".to_string(),
+                content: "# Hello".to_string(),
+                slug: None,
+                description: Some("About".to_string()),
+                tags: vec![],
+            },
+        );
+
+        assert_round_trip(
+            &json!({ "title": "Hello", "content": "# Hello", "description": null }),
+            &PreviewRequest {
+                title: "Hello".to_string(),
+                content: "# Hello".to_string(),
+                description: None,
+            },
+        );
+    }
+
+    #[test]
+    pub fn optional_fields_can_be_left_out() {
+        let request: PostCreateRequest =
+            serde_json::from_value(json!({ "id": ID, "title": "Hello", "content": "" })).unwrap();
+
+        assert_eq!(None, request.slug);
+        assert_eq!(Vec::<String>::new(), request.tags);
+
+        let request: PostPutRequest =
+            serde_json::from_value(json!({ "title": "Hello", "content": "" })).unwrap();
+
+        assert_eq!(None, request.date_published);
+    }
+
+    #[test]
+    pub fn hashes_the_content() {
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            content_sha256("")
+        );
+        assert_eq!(content_sha256("# Hello"), media_sha256(b"# Hello"));
+    }
+
+    #[test]
+    pub fn finds_media_types() {
+        assert_eq!(Some("image/png"), media_type("png"));
+        assert_eq!(Some("image/jpeg"), media_type("JPG"));
+        assert_eq!(None, media_type("html"));
+    }
+}
@@ -0,0 +1,278 @@
+use std::time::Duration;
+
+use reqwest::{
+    header::{self, HeaderValue},
+    Method, RequestBuilder, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        media_sha256, DraftPutRequest, DraftResponse, MediaResponse, PostPutRequest, PostResponse,
+        PostSummaryResponse, PostUpdateRequest, PostsQuery, PreviewRequest,
+    },
+    signing,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Invalid URL: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] reqwest::header::InvalidHeaderValue),
+    #[error("Invalid token, it should be <prefix>.<secret>")]
+    MalformedToken,
+    /// When waiting would take longer than [`MAX_RETRY_AFTER`], e.g. a lockout after too many
+    /// failed attempts
+    #[error("Too many requests, retry in {0} seconds")]
+    RateLimited(u64),
+    /// With the detail from the problem details returned by the API
+    #[error("{status}{}", detail.as_ref().map_or_else(String::new, |x| format!(": {x}")))]
+    Api {
+        status: StatusCode,
+        detail: Option<String>,
+    },
+}
+
+const SIGNATURE_REJECTED: &str =
+    "The signature was rejected, if the token predates request signing, rotate it or use --no-sign";
+
+/// How many times a rate limited request is sent.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// The longest `Retry-After` that is waited for, a longer one fails the request.
+const MAX_RETRY_AFTER: u64 = 30;
+
+#[derive(Deserialize)]
+struct ProblemDetails {
+    detail: Option<String>,
+}
+
+/// The posts API of one blog.
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    token: String,
+    sign: bool,
+}
+
+impl Client {
+    /// Requests are signed with the token, unless `sign` is `false`, then the token itself is sent
+    /// in `X-Token`.
+    #[must_use]
+    pub fn new(base_url: Url, token: String, sign: bool) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url,
+            token,
+            sign,
+        }
+    }
+
+    /// Retries the rate limited requests after the time from `Retry-After`, so that commands
+    /// sending many requests, like `sync`, slow down instead of failing halfway. Only short waits
+    /// are retried, so that a lockout fails right away instead of hanging.
+    async fn send(&self, builder: RequestBuilder) -> Result<reqwest::Response, Error> {
+        let mut attempt = 1;
+        let response = loop {
+            let mut request = builder
+                .try_clone()
+                .expect("The request bodies are not streams")
+                .build()?;
+
+            // Signed every time, as the signature covers the timestamp
+            if self.sign {
+                signing::sign(&mut request, &self.token)?;
+            } else {
+                request
+                    .headers_mut()
+                    .insert("X-Token", HeaderValue::from_str(&self.token)?);
+            }
+
+            let response = self.http.execute(request).await?;
+            let retry_after = response
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|x| x.to_str().ok()?.parse().ok());
+
+            match retry_after {
+                Some(seconds) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    if seconds > MAX_RETRY_AFTER {
+                        return Err(Error::RateLimited(seconds));
+                    }
+                    if attempt >= MAX_ATTEMPTS {
+                        break response;
+                    }
+
+                    eprintln!("Rate limited, retrying in {seconds} seconds");
+                    tokio::time::sleep(Duration::from_secs(seconds)).await;
+                    attempt += 1;
+                }
+                _ => break response,
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let mut detail = response
+            .json::<ProblemDetails>()
+            .await
+            .ok()
+            .and_then(|x| x.detail);
+        // The tokens issued before the requests were signed can't sign them
+        if status == StatusCode::UNAUTHORIZED && self.sign && detail.is_none() {
+            detail = Some(SIGNATURE_REJECTED.to_string());
+        }
+
+        Err(Error::Api { status, detail })
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&(impl Serialize + Sync)>,
+    ) -> Result<T, Error> {
+        let mut builder = self.http.request(method, self.base_url.join(path)?);
+        if let Some(body) = body {
+            builder = builder.json(body);
+        }
+
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    pub async fn list_posts(&self, query: PostsQuery) -> Result<Vec<PostSummaryResponse>, Error> {
+        let builder = self
+            .http
+            .get(self.base_url.join("api/posts")?)
+            .query(&query);
+
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    pub async fn get_post(&self, id: Uuid) -> Result<PostResponse, Error> {
+        self.request(Method::GET, &format!("api/posts/{id}"), None::<&()>)
+            .await
+    }
+
+    /// Creates the post, or replaces the existing one with the same ID. Returns `true` if it was
+    /// created.
+    pub async fn put_post(
+        &self,
+        id: Uuid,
+        post: &PostPutRequest,
+    ) -> Result<(PostResponse, bool), Error> {
+        let builder = self
+            .http
+            .put(self.base_url.join(&format!("api/posts/{id}"))?)
+            .json(post);
+        let response = self.send(builder).await?;
+        let created = response.status() == StatusCode::CREATED;
+
+        Ok((response.json().await?, created))
+    }
+
+    /// Replaces the draft of the post, the post itself doesn't change.
+    pub async fn put_draft(
+        &self,
+        id: Uuid,
+        draft: &DraftPutRequest,
+    ) -> Result<DraftResponse, Error> {
+        self.request(Method::PUT, &format!("api/drafts/{id}"), Some(draft))
+            .await
+    }
+
+    /// Where the draft is shown, for the logged in users.
+    pub fn draft_url(&self, id: Uuid) -> Result<Url, Error> {
+        Ok(self.base_url.join(&format!("admin/drafts/{id}"))?)
+    }
+
+    /// The HTML of the public page.
+    pub async fn preview(&self, preview: &PreviewRequest) -> Result<String, Error> {
+        let builder = self
+            .http
+            .post(self.base_url.join("api/preview")?)
+            .json(preview);
+
+        Ok(self.send(builder).await?.text().await?)
+    }
+
+    /// Whether the blog has the media file already, asked the public route, as it doesn't need any
+    /// scope.
+    pub async fn has_media(&self, sha256: &str) -> Result<bool, Error> {
+        let response = self
+            .http
+            .head(self.base_url.join(&format!("media/{sha256}"))?)
+            .send()
+            .await?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(false),
+            status if status.is_success() => Ok(true),
+            status => Err(Error::Api {
+                status,
+                detail: None,
+            }),
+        }
+    }
+
+    /// Uploads the media file under the hash of its content. Returns `true` if the blog didn't
+    /// have it yet.
+    pub async fn put_media(
+        &self,
+        content_type: &str,
+        content: Vec<u8>,
+    ) -> Result<(MediaResponse, bool), Error> {
+        let sha256 = media_sha256(&content);
+        let builder = self
+            .http
+            .put(self.base_url.join(&format!("api/media/{sha256}"))?)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(content);
+        let response = self.send(builder).await?;
+        let created = response.status() == StatusCode::CREATED;
+
+        Ok((response.json().await?, created))
+    }
+
+    /// A file served by the blog, like the CSS, it doesn't need the token.
+    pub async fn asset(&self, path: &str) -> Result<reqwest::Response, Error> {
+        Ok(self.http.get(self.base_url.join(path)?).send().await?)
+    }
+
+    pub async fn update_post(
+        &self,
+        id: Uuid,
+        update: &PostUpdateRequest,
+    ) -> Result<PostResponse, Error> {
+        self.request(Method::PATCH, &format!("api/posts/{id}"), Some(update))
+            .await
+    }
+
+    /// The post can be restored later.
+    pub async fn delete_post(&self, id: Uuid) -> Result<(), Error> {
+        let builder = self
+            .http
+            .delete(self.base_url.join(&format!("api/posts/{id}"))?);
+        self.send(builder).await?;
+
+        Ok(())
+    }
+
+    pub async fn restore_post(&self, id: Uuid) -> Result<PostResponse, Error> {
+        self.request(
+            Method::POST,
+            &format!("api/posts/{id}/restore"),
+            None::<&()>,
+        )
+        .await
+    }
+}
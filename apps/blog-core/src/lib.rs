@@ -0,0 +1,14 @@
+#![deny(clippy::all, clippy::pedantic, clippy::nursery)]
+// Only used by the apps in this workspace, not documented like a published crate
+#![allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
+
+//! What the blog backend and the admin CLI share: the API types, the markdown rendering and the
+//! API client.
+
+pub mod api;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "client")]
+mod signing;
@@ -0,0 +1,364 @@
+//! Markdown to HTML, with a table of contents made of the headings.
+
+use std::{collections::HashMap, fmt::Write, ops::RangeInclusive, sync::Mutex};
+
+use comrak::{format_html_with_plugins, nodes::NodeValue, parse_document, Arena, Options, Plugins};
+
+#[derive(Eq, PartialEq, Debug)]
+struct TocItem {
+    title: String,
+    anchor: String,
+    children: Vec<Self>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Heading {
+    content: String,
+    slug: String,
+    level: u8,
+}
+
+struct HeadingAdapter {
+    headings: Mutex<Vec<Heading>>,
+}
+
+impl comrak::adapters::HeadingAdapter for HeadingAdapter {
+    fn enter(
+        &self,
+        output: &mut dyn std::io::Write,
+        heading: &comrak::adapters::HeadingMeta,
+        _sourcepos: Option<comrak::nodes::Sourcepos>,
+    ) -> std::io::Result<()> {
+        let slug = slug::slugify(&heading.content);
+        self.headings.lock().unwrap().push(Heading {
+            content: heading.content.clone(),
+            slug: slug.clone(),
+            level: heading.level,
+        });
+        write!(output, "<h{} id=\"{}\">", heading.level, slug)
+    }
+
+    fn exit(
+        &self,
+        output: &mut dyn std::io::Write,
+        heading: &comrak::adapters::HeadingMeta,
+    ) -> std::io::Result<()> {
+        write!(output, "</h{}>", heading.level)
+    }
+}
+
+fn toc_to_html(toc: Vec<TocItem>) -> String {
+    let mut result = String::new();
+
+    for item in toc {
+        let children = if item.children.is_empty() {
+            String::new()
+        } else {
+            format!("<ul>{}</ul>", toc_to_html(item.children))
+        };
+
+        write!(
+            result,
+            "<li><a href=\"#{}\">{}</a>{}</li>",
+            item.anchor, item.title, children
+        )
+        .unwrap();
+    }
+
+    result
+}
+
+fn generate_toc(headings: Vec<Heading>) -> Vec<TocItem> {
+    let min_level = headings.iter().map(|x| x.level).min().unwrap_or(0);
+
+    let mut output: Vec<TocItem> = vec![];
+    let mut lvl_map: HashMap<String, u8> = HashMap::new();
+
+    for h in headings.into_iter().map(|x| Heading {
+        level: x.level - min_level,
+        ..x
+    }) {
+        lvl_map.insert(h.content.clone(), h.level);
+
+        let last = output.last_mut();
+        let slug = h.slug.clone();
+
+        if let Some(mut last) = last {
+            let lvl = *lvl_map.get(&last.title).unwrap();
+            if lvl < h.level {
+                loop {
+                    let len = last.children.len();
+
+                    if len > 0 {
+                        let last_ = last.children.last_mut().unwrap();
+                        let lvl = *lvl_map.get(&last_.title).unwrap();
+
+                        if lvl < h.level {
+                            last = last.children.last_mut().unwrap();
+                            continue;
+                        }
+                    }
+
+                    break;
+                }
+
+                last.children.push(TocItem {
+                    title: h.content.clone(),
+                    anchor: h.slug,
+                    children: vec![],
+                });
+            } else {
+                output.push(TocItem {
+                    title: h.content.clone(),
+                    anchor: slug,
+                    children: vec![],
+                });
+            }
+        } else {
+            output.push(TocItem {
+                title: h.content.clone(),
+                anchor: slug,
+                children: vec![],
+            });
+        }
+    }
+    output
+}
+
+/// The HTML of a post, the table of contents is a `<ul>` linking to the `id`s of the headings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendered {
+    pub content: String,
+    pub toc: String,
+}
+
+#[must_use]
+pub fn render(markdown: &str) -> Rendered {
+    let arena = Arena::new();
+
+    let root = parse_document(&arena, markdown, &Options::default());
+
+    let mut plugins = Plugins::default();
+    let heading_adapter = HeadingAdapter {
+        headings: Mutex::new(vec![]),
+    };
+    plugins.render.heading_adapter = Some(&heading_adapter);
+
+    let mut html = vec![];
+    format_html_with_plugins(root, &Options::default(), &mut html, &plugins).unwrap();
+
+    let toc = generate_toc(heading_adapter.headings.lock().unwrap().clone());
+
+    Rendered {
+        content: String::from_utf8(html).unwrap(),
+        toc: format!("<ul>{}</ul>", toc_to_html(toc)),
+    }
+}
+
+/// Where a link or an image in the markdown points to, as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub url: String,
+    pub image: bool,
+}
+
+/// The links and the images, in the order they're written, without the ones in code.
+#[must_use]
+pub fn links(markdown: &str) -> Vec<Link> {
+    let arena = Arena::new();
+
+    let root = parse_document(&arena, markdown, &Options::default());
+
+    root.descendants()
+        .filter_map(|node| match &node.data.borrow().value {
+            NodeValue::Link(link) => Some(Link {
+                url: link.url.clone(),
+                image: false,
+            }),
+            NodeValue::Image(link) => Some(Link {
+                url: link.url.clone(),
+                image: true,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The lines of the code blocks and the HTML blocks, counted from 1, nothing in them is a link.
+#[must_use]
+pub fn code_block_lines(markdown: &str) -> Vec<RangeInclusive<usize>> {
+    let arena = Arena::new();
+
+    let root = parse_document(&arena, markdown, &Options::default());
+
+    root.descendants()
+        .filter_map(|node| {
+            let data = node.data.borrow();
+            if !matches!(
+                data.value,
+                NodeValue::CodeBlock(_) | NodeValue::HtmlBlock(_)
+            ) {
+                return None;
+            }
+
+            // An indented block ends at the column 0 of the next line
+            let end = &data.sourcepos.end;
+            let last = if end.column == 0 {
+                end.line - 1
+            } else {
+                end.line
+            };
+
+            Some(data.sourcepos.start.line..=last)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    pub fn can_generate_toc() {
+        let headings = vec![
+            Heading {
+                content: "abc1".to_string(),
+                slug: "abc1".to_string(),
+                level: 2,
+            },
+            Heading {
+                content: "abc2".to_string(),
+                slug: "abc1".to_string(),
+                level: 2,
+            },
+            Heading {
+                content: "abc3".to_string(),
+                slug: "abc1".to_string(),
+                level: 3,
+            },
+            Heading {
+                content: "abc4".to_string(),
+                slug: "abc1".to_string(),
+                level: 4,
+            },
+            Heading {
+                content: "abc5".to_string(),
+                slug: "abc1".to_string(),
+                level: 4,
+            },
+            Heading {
+                content: "abc6".to_string(),
+                slug: "abc1".to_string(),
+                level: 5,
+            },
+            Heading {
+                content: "abc7".to_string(),
+                slug: "abc1".to_string(),
+                level: 2,
+            },
+        ];
+
+        let expected = vec![
+            TocItem {
+                title: "abc1".to_string(),
+                anchor: "abc1".to_string(),
+                children: vec![],
+            },
+            TocItem {
+                title: "abc2".to_string(),
+                anchor: "abc1".to_string(),
+                children: vec![TocItem {
+                    title: "abc3".to_string(),
+                    anchor: "abc1".to_string(),
+                    children: vec![
+                        TocItem {
+                            title: "abc4".to_string(),
+                            anchor: "abc1".to_string(),
+                            children: vec![],
+                        },
+                        TocItem {
+                            title: "abc5".to_string(),
+                            anchor: "abc1".to_string(),
+                            children: vec![TocItem {
+                                title: "abc6".to_string(),
+                                anchor: "abc1".to_string(),
+                                children: vec![],
+                            }],
+                        },
+                    ],
+                }],
+            },
+            TocItem {
+                title: "abc7".to_string(),
+                anchor: "abc1".to_string(),
+                children: vec![],
+            },
+        ];
+
+        let toc = generate_toc(headings);
+
+        pretty_assertions::assert_eq!(expected, toc);
+    }
+    #[test]
+    pub fn finds_links() {
+        let markdown = "![A diagram](./diagram.png \"Title\")\n\n\
+            See [the paper][paper] and `[not](a-link.md)`.\n\n\
+            ```\n![](code.png)\n```\n\n\
+            [paper]: https://example.com/paper.pdf\n";
+
+        assert_eq!(
+            vec![
+                Link {
+                    url: "./diagram.png".to_string(),
+                    image: true,
+                },
+                Link {
+                    url: "https://example.com/paper.pdf".to_string(),
+                    image: false,
+                },
+            ],
+            links(markdown)
+        );
+    }
+
+    #[test]
+    pub fn finds_code_blocks() {
+        let markdown =
+            "Text\n\n```\n[a](a.png)\n```\n\n    [b](b.png)\n\n<div>\n[c](c.png)\n</div>\n";
+
+        assert_eq!(vec![3..=5, 7..=7, 9..=11], code_block_lines(markdown));
+    }
+
+    #[test]
+    pub fn can_convert_toc_to_html() {
+        let toc = vec![
+            TocItem {
+                title: "a".to_string(),
+                anchor: "a".to_string(),
+                children: vec![],
+            },
+            TocItem {
+                title: "b".to_string(),
+                anchor: "b".to_string(),
+                children: vec![
+                    TocItem {
+                        title: "c".to_string(),
+                        anchor: "c".to_string(),
+                        children: vec![],
+                    },
+                    TocItem {
+                        title: "d".to_string(),
+                        anchor: "d".to_string(),
+                        children: vec![],
+                    },
+                ],
+            },
+        ];
+        let html = toc_to_html(toc);
+
+        // TODO: this function should include the outer <ul />
+        assert_eq!("<li><a href=\"#a\">a</a></li><li><a href=\"#b\">b</a><ul><li><a href=\"#c\">c</a></li><li><a href=\"#d\">d</a></li></ul></li>", html);
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use reqwest::{header::HeaderValue, Request};
+use sha2::{Digest, Sha256};
+
+use crate::{api::SIGNING_KEY_LABEL, client::Error};
+
+const LEGACY_PREFIX_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 32;
+
+/// Tokens are `<prefix>.<secret>`, the ones issued before the secrets were hashed have no
+/// separator and a 16 character prefix.
+fn split_token(token: &str) -> Option<(&str, &str)> {
+    let (prefix, secret) = token.split_once('.').or_else(|| {
+        (token.len() > LEGACY_PREFIX_LENGTH && token.is_char_boundary(LEGACY_PREFIX_LENGTH))
+            .then(|| token.split_at(LEGACY_PREFIX_LENGTH))
+    })?;
+
+    (!prefix.is_empty() && !secret.is_empty()).then_some((prefix, secret))
+}
+
+fn canonical_request(
+    method: &str,
+    path_and_query: &str,
+    timestamp: u64,
+    nonce: &str,
+    body: &[u8],
+) -> String {
+    let body_digest = Sha256::digest(body);
+
+    format!("{method}\n{path_and_query}\n{timestamp}\n{nonce}\n{body_digest:x}")
+}
+
+/// Adds the `X-Signature` header, so that the token itself is never sent. The key is derived from
+/// the secret, see [`SIGNING_KEY_LABEL`].
+pub fn sign(request: &mut Request, token: &str) -> Result<(), Error> {
+    let (prefix, secret) = split_token(token).ok_or(Error::MalformedToken)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let nonce: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(NONCE_LENGTH)
+        .map(char::from)
+        .collect();
+
+    let url = request.url();
+    let path_and_query = url.query().map_or_else(
+        || url.path().to_string(),
+        |query| format!("{}?{}", url.path(), query),
+    );
+    let body = request
+        .body()
+        .map(|x| x.as_bytes().expect("Streaming bodies can't be signed"))
+        .unwrap_or_default();
+
+    let mut key = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    key.update(SIGNING_KEY_LABEL.as_bytes());
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key.finalize().into_bytes()).unwrap();
+    mac.update(
+        canonical_request(
+            request.method().as_str(),
+            &path_and_query,
+            timestamp,
+            &nonce,
+            body,
+        )
+        .as_bytes(),
+    );
+    let signature = STANDARD.encode(mac.finalize().into_bytes());
+
+    request.headers_mut().insert(
+        "X-Signature",
+        HeaderValue::from_str(&format!(
+            "key_id={prefix}, timestamp={timestamp}, nonce={nonce}, signature={signature}"
+        ))?,
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn splits_tokens() {
+        assert_eq!(Some(("abc", "def")), split_token("abc.def"));
+        assert_eq!(
+            Some(("0123456789abcdef", "rest")),
+            split_token("0123456789abcdefrest")
+        );
+        assert_eq!(None, split_token("short"));
+        assert_eq!(None, split_token(".secret"));
+        assert_eq!(None, split_token("prefix."));
+        assert_eq!(None, split_token("aééééééééé"));
+    }
+}
@@ -18,10 +18,15 @@
       || (builtins.match ".*/templates/.*" path != null) 
       || craneLib.filterCargoSources path type;
       packageArguments = {
+        pname = "backend";
+        version = "0.1.0";
         src = pkgs.lib.cleanSourceWith {
-          src = craneLib.path ./apps/backend;
+          src = craneLib.path ./apps;
           filter = sourceFilter;
         };
+        cargoExtraArgs = "--package backend";
+        nativeBuildInputs = [ pkgs.pkg-config ];
+        buildInputs = [ pkgs.openssl ];
       };
       cargoArtifacts = craneLib.buildDepsOnly packageArguments;
       backendPackage = craneLib.buildPackage (packageArguments // {